@@ -1,11 +1,16 @@
 //! Descriptive block is always the first line of a valid .aba file
 use lazy_static::lazy_static;
 use nom::{bytes::complete::take, IResult};
-use std::{fmt::Display, fmt::Write};
+use std::{collections::BTreeMap, fmt::Display};
+use time::Date;
 
+use crate::calendar::*;
+use crate::context::*;
 use crate::csv::*;
 use crate::errors::*;
+use crate::helper::*;
 use crate::parser_utils::*;
+use crate::report::*;
 use crate::types::*;
 
 lazy_static! {
@@ -57,81 +62,82 @@ impl DescriptiveBlock {
         Ok((i, descriptive))
     }
 
-    pub async fn validate(&self) -> Result<String, LineParseError> {
-        let mut res: String = String::new();
+    pub async fn validate(
+        &self,
+        ctx: &ValidationContext,
+    ) -> Result<Vec<Diagnostic>, LineParseError> {
+        let mut res: Vec<Diagnostic> = Vec::new();
 
         let _res = validate_generic_filler_str(
             self.record_type.clone(),
             "0".to_owned(),
             ValidationType::DescriptiveRecordTypeZero,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.record_type)));
 
         let _res = validate_generic_filler_str(
             self.blank_1.clone(),
             BLANK_1.to_owned(),
             ValidationType::DescriptiveBlankOne,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.blank_1)));
 
         let _res = validate_generic_filler_str(
             self.reel_seq.clone(),
             "01".to_owned(),
             ValidationType::DescriptiveReelSequence,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.reel_seq)));
 
         let _res = validate_user_name(self.bank_name.clone(), ValidationType::DescriptiveUsername)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+            .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.bank_name)));
 
         let _res = validate_generic_filler_str(
             self.blank_2.clone(),
             BLANK_2.to_owned(),
             ValidationType::DescriptiveBlankTwo,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.blank_2)));
 
         let _res = validate_self_name(self.user_name.clone(), ValidationType::DescriptiveSelfName)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+            .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.user_name)));
+
+        let _res = validate_becs_charset(
+            self.user_name.clone(),
+            ValidationType::DescriptiveSelfName,
+            &1u32,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.user_name)));
 
         let _res = validate_apca_ident(self.apca_number.clone(), ValidationType::DescriptiveApca)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+            .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.apca_number)));
 
         let _res = validate_file_entry(
             self.file_description.clone(),
             ValidationType::DescriptiveEntry,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.file_description)));
 
-        let _res = validate_date(self.settle_date.clone(), ValidationType::DescriptiveDate)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_becs_charset(
+            self.file_description.clone(),
+            ValidationType::DescriptiveEntry,
+            &1u32,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.file_description)));
+
+        let _res = validate_date(
+            self.settle_date.clone(),
+            ValidationType::DescriptiveDate,
+            &ctx.window,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.settle_date)));
 
         let _res = validate_generic_filler_str(
             self.blank_3.clone(),
             BLANK_3.to_owned(),
             ValidationType::DescriptiveBlankThree,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, 1u32, &self.blank_3)));
 
         Ok(res)
     }
@@ -156,6 +162,67 @@ impl Display for DescriptiveBlock {
     }
 }
 
+impl DescriptiveBlock {
+    /// Warns about settlement dates that are in the past or not business days in the calendar
+    pub async fn validate_processing_day(
+        &self,
+        calendar: &HolidayCalendar,
+        today: Date,
+        window: &CenturyWindow,
+    ) -> Vec<Diagnostic> {
+        let mut res: Vec<Diagnostic> = Vec::new();
+
+        let _res = validate_processing_day(self.settle_date.clone(), calendar, today, window)
+            .map_err(|e| res.push(Diagnostic::warning(&e, 1u32, &self.settle_date)));
+
+        res
+    }
+
+    /// Cross-checks the bank name against the institution of every distinct trace BSB,
+    /// keyed by trace BSB with the first line it appears at. Institutions guessed from the
+    /// BSB prefix are only warned about
+    pub async fn validate_trace_bsbs(
+        &self,
+        trace_bsbs: &BTreeMap<String, u32>,
+        ctx: &ValidationContext,
+    ) -> Vec<Diagnostic> {
+        let mut res: Vec<Diagnostic> = Vec::new();
+
+        for (bsb, line_count) in trace_bsbs.iter() {
+            let _res = validate_bank_trace_bsb(
+                self.bank_name.clone(),
+                bsb.clone(),
+                line_count,
+                &ctx.directory,
+            )
+            .map_err(|e| match e {
+                LineParseError::DescriptiveBankTraceBsbGuessed(..) => {
+                    res.push(Diagnostic::warning(&e, 1u32, &self.bank_name))
+                }
+                _ => res.push(Diagnostic::new(&e, 1u32, &self.bank_name)),
+            });
+        }
+
+        res
+    }
+
+    /// Resets the record type, reel sequence and fillers, re-justifies and uppercases the rest
+    pub fn fix(&self) -> Self {
+        Self {
+            record_type: "0".to_owned(),
+            blank_1: BLANK_1.to_owned(),
+            reel_seq: "01".to_owned(),
+            bank_name: self.bank_name.to_uppercase(),
+            blank_2: BLANK_2.to_owned(),
+            user_name: fix_text(&self.user_name, 26usize),
+            apca_number: fix_numeric(&self.apca_number, 6usize, FillStrategy::Zero),
+            file_description: fix_text(&self.file_description, 12usize),
+            settle_date: self.settle_date.clone(),
+            blank_3: BLANK_3.to_owned(),
+        }
+    }
+}
+
 impl From<SettlementSettings> for DescriptiveBlock {
     fn from(desc_settings: SettlementSettings) -> Self {
         Self {
@@ -180,6 +247,6 @@ impl From<SettlementSettings> for DescriptiveBlock {
 #[test]
 fn test_descriptive() {
     let desc: &str = "0                 01BQL       MY NAME                   1111111004231633  230410                                        ";
-    let (_, result) = DescriptiveBlock::deserialise(&desc).unwrap();
+    let (_, result) = DescriptiveBlock::deserialise(desc).unwrap();
     assert_eq!(result.blank_3, *BLANK_3)
 }
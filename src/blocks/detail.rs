@@ -1,10 +1,13 @@
 //! Detail block starts from the second line to the second last line of a valid .aba file
 use nom::{bytes::complete::take, IResult};
-use std::{fmt::Display, fmt::Write};
+use std::fmt::Display;
 
+use crate::context::*;
 use crate::csv::*;
 use crate::errors::*;
+use crate::model::{Cents, TransactionCode};
 use crate::parser_utils::*;
+use crate::report::*;
 use crate::types::*;
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
@@ -56,8 +59,12 @@ impl DetailBlock {
         Ok((i, detail))
     }
 
-    pub async fn validate(&self, line_count: &u32) -> Result<String, LineParseError> {
-        let mut res: String = String::new();
+    pub async fn validate(
+        &self,
+        line_count: &u32,
+        ctx: &ValidationContext,
+    ) -> Result<Vec<Diagnostic>, LineParseError> {
+        let mut res: Vec<Diagnostic> = Vec::new();
 
         let _res = validate_filler_str_line_counted(
             self.record_type.clone(),
@@ -65,100 +72,107 @@ impl DetailBlock {
             ValidationType::DetailRecordTypeOne,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.record_type)));
 
-        let _res = validate_bsb_client(self.bsb_number.clone(), BsbType::DetailBsb, line_count)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_bsb_client(
+            self.bsb_number.clone(),
+            BsbType::DetailBsb,
+            line_count,
+            &ctx.directory,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.bsb_number)));
 
         let _res = validate_dest_acct(
             self.dest_acct.clone(),
             ValidationType::DetailDestAccount,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.dest_acct)));
 
         let _res = validate_indicator(
             self.indicator.clone(),
             ValidationType::DetailIndicator,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.indicator)));
 
         let _res = validate_trans_code(
             self.trans_code.clone(),
             ValidationType::DetailTransCode,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.trans_code)));
 
         let _res = validate_amount(
             self.amount.clone(),
             ValidationType::DetailAmount,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.amount)));
 
         let _res = validate_client_name(
             self.client_name.clone(),
             ValidationType::DetailClientName,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.client_name)));
+
+        let _res = validate_becs_charset(
+            self.client_name.clone(),
+            ValidationType::DetailClientName,
+            line_count,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.client_name)));
 
         let _res = validate_lodge_ref(
             self.lodge_ref.clone(),
             ValidationType::DetailLodgeRef,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.lodge_ref)));
 
-        let _res = validate_bsb_trace(self.trace_bsb.clone(), BsbType::DetailTraceBsb, line_count)
-            .map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_becs_charset(
+            self.lodge_ref.clone(),
+            ValidationType::DetailLodgeRef,
+            line_count,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.lodge_ref)));
+
+        let _res = validate_bsb_trace(
+            self.trace_bsb.clone(),
+            BsbType::DetailTraceBsb,
+            line_count,
+            &ctx.directory,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.trace_bsb)));
 
         let _res = validate_src_acct(
             self.src_acct.clone(),
             ValidationType::DetailSrcAccount,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.src_acct)));
 
         let _res = validate_remitter(
             self.account_name.clone(),
             ValidationType::DetailRemitter,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.account_name)));
+
+        let _res = validate_becs_charset(
+            self.account_name.clone(),
+            ValidationType::DetailRemitter,
+            line_count,
+        )
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.account_name)));
 
         let _res = validate_tax_withhold(
             self.tax_withhold.clone(),
             ValidationType::DetailTaxWithhold,
             line_count,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, *line_count, &self.tax_withhold)));
 
         Ok(res)
     }
@@ -185,25 +199,69 @@ impl Display for DetailBlock {
     }
 }
 
+impl DetailBlock {
+    /// Amount split into its credit and debit part, both zero when the amount or the
+    /// transaction code is malformed
+    pub fn credit_debit(&self) -> (Cents, Cents) {
+        self.checked_credit_debit()
+            .unwrap_or((Cents(0u64), Cents(0u64)))
+    }
+
+    /// Amount split into its credit and debit part, None when the amount or the transaction
+    /// code is malformed
+    pub fn checked_credit_debit(&self) -> Option<(Cents, Cents)> {
+        match (
+            Cents::from_field(&self.amount, 10usize),
+            self.trans_code.parse::<TransactionCode>(),
+        ) {
+            (Some(amount), Ok(code)) if code.is_debit() => Some((Cents(0u64), amount)),
+            (Some(amount), Ok(_)) => Some((amount, Cents(0u64))),
+            _ => None,
+        }
+    }
+
+    /// Re-justifies and uppercases every field, numeric fields are only re-justified when
+    /// they hold digits so that a blank amount is never turned into zero
+    pub fn fix(&self) -> Self {
+        Self {
+            record_type: "1".to_owned(),
+            bsb_number: self.bsb_number.clone(),
+            dest_acct: fix_numeric(&self.dest_acct, 9usize, FillStrategy::Blank),
+            indicator: self.indicator.to_uppercase(),
+            trans_code: self.trans_code.clone(),
+            amount: fix_numeric(&self.amount, 10usize, FillStrategy::Zero),
+            client_name: fix_text(&self.client_name, 32usize),
+            lodge_ref: fix_text(&self.lodge_ref, 18usize),
+            trace_bsb: self.trace_bsb.clone(),
+            src_acct: fix_numeric(&self.src_acct, 9usize, FillStrategy::Blank),
+            account_name: fix_text(&self.account_name, 16usize),
+            tax_withhold: match self.tax_withhold.trim().is_empty() {
+                true => "0".repeat(8),
+                false => fix_numeric(&self.tax_withhold, 8usize, FillStrategy::Zero),
+            },
+        }
+    }
+}
+
 impl From<RecordWithConf> for DetailBlock {
     fn from(rec_conf: RecordWithConf) -> Self {
         Self {
             record_type: "1".to_owned(),
             bsb_number: rec_conf.rec.bsb,
             dest_acct: right_adjust(&rec_conf.rec.account_number, 9usize, FillStrategy::Blank),
-            indicator: " ".to_owned(),
-            trans_code: "53".to_owned(),
+            indicator: rec_conf.rec.indicator,
+            trans_code: rec_conf.rec.trans_code,
             amount: right_adjust(&rec_conf.rec.amount, 10usize, FillStrategy::Zero),
             client_name: left_adjust(&rec_conf.rec.client_name, 32usize, FillStrategy::Blank),
             lodge_ref: left_adjust(&rec_conf.rec.comment, 18usize, FillStrategy::Blank),
-            trace_bsb: rec_conf.conf.trace_bsb,
+            trace_bsb: rec_conf.rec.trace_bsb,
             src_acct: right_adjust(
-                &rec_conf.conf.trace_account_number,
+                &rec_conf.rec.trace_account_number,
                 9usize,
                 FillStrategy::Blank,
             ),
             account_name: left_adjust(
-                &rec_conf.conf.trace_account_name,
+                &rec_conf.rec.trace_account_name,
                 16usize,
                 FillStrategy::Blank,
             ),
@@ -215,6 +273,23 @@ impl From<RecordWithConf> for DetailBlock {
 #[test]
 fn test_detail_deser() {
     let detail: &'static str = "1123-456157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200";
-    let (_, result) = DetailBlock::deserialise(&detail).unwrap();
+    let (_, result) = DetailBlock::deserialise(detail).unwrap();
     assert_eq!(result.bsb_number, "123-456")
 }
+
+#[test]
+fn test_detail_fix() {
+    let detail: &'static str = "1123-456 57108231 53    1234  s r smith                         test batch      062-000  2223123  my account    00001200";
+    let (_, result) = DetailBlock::deserialise(detail).unwrap();
+    assert_eq!(
+        result.fix().to_string(),
+        "1123-456 57108231 530000001234S R SMITH                       TEST BATCH        062-000  2223123MY ACCOUNT      00001200"
+    );
+    assert_eq!(result.fix().credit_debit(), (Cents(1234), Cents(0)));
+    assert_eq!(result.credit_debit(), (Cents(0), Cents(0)));
+    let result = DetailBlock {
+        trans_code: "13".to_owned(),
+        ..result.fix()
+    };
+    assert_eq!(result.credit_debit(), (Cents(0), Cents(1234)));
+}
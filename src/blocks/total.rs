@@ -1,11 +1,15 @@
 //! Total block is always the last line of a valid .aba file
 use lazy_static::lazy_static;
 use nom::{bytes::complete::take, IResult};
-use std::{fmt::Display, fmt::Write};
+use std::fmt::Display;
 
+use crate::blocks::*;
 use crate::csv::*;
 use crate::errors::*;
+use crate::helper::*;
+use crate::model::Cents;
 use crate::parser_utils::*;
+use crate::report::*;
 use crate::types::*;
 
 lazy_static! {
@@ -48,77 +52,116 @@ impl TotalBlock {
         Ok((i, total))
     }
 
-    pub async fn validate(&self, line_count: &u32) -> Result<String, LineParseError> {
-        let mut res: String = String::new();
+    pub async fn validate(&self, line_count: &u32) -> Result<Vec<Diagnostic>, LineParseError> {
+        let mut res: Vec<Diagnostic> = Vec::new();
+        let total_line = line_count + 2u32;
+        let (_, amounts) = TotalField::deserialise(&self.total_field).unwrap_or_default();
 
         let _res = validate_generic_filler_str(
             self.record_type.clone(),
             "7".to_owned(),
             ValidationType::TotalTypeSeven,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.record_type)));
 
         let _res = validate_generic_filler_str(
             self.bsb_filler.clone(),
             "999-999".to_owned(),
             ValidationType::TotalBsbFiller,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.bsb_filler)));
 
         let _res = validate_generic_filler_str(
             self.blank_1.clone(),
             BLANK_1.to_owned(),
             ValidationType::TotalBlankOne,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.blank_1)));
 
-        let _res =
-            validate_total_field(self.total_field.clone(), TotalAmountType::Total).map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_total_field(self.total_field.clone(), TotalAmountType::Total)
+            .map_err(|e| res.push(Diagnostic::new(&e, total_line, &amounts.total)));
 
-        let _res =
-            validate_total_field(self.total_field.clone(), TotalAmountType::Credit).map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_total_field(self.total_field.clone(), TotalAmountType::Credit)
+            .map_err(|e| res.push(Diagnostic::new(&e, total_line, &amounts.credit)));
 
-        let _res =
-            validate_total_field(self.total_field.clone(), TotalAmountType::Debit).map_err(|e| {
-                res.write_str(&e.to_string()).unwrap();
-            });
+        let _res = validate_total_field(self.total_field.clone(), TotalAmountType::Debit)
+            .map_err(|e| res.push(Diagnostic::new(&e, total_line, &amounts.debit)));
 
         let _res = validate_generic_filler_str(
             self.blank_2.clone(),
             BLANK_2.to_owned(),
             ValidationType::TotalBlankTwo,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.blank_2)));
 
-        let _res = validate_record_type_count(self.record_count.clone(), line_count).map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        let _res = validate_record_type_count(self.record_count.clone(), line_count)
+            .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.record_count)));
 
         let _res = validate_generic_filler_str(
             self.blank_3.clone(),
             BLANK_3.to_owned(),
             ValidationType::TotalBlankThree,
         )
-        .map_err(|e| {
-            res.write_str(&e.to_string()).unwrap();
-        });
+        .map_err(|e| res.push(Diagnostic::new(&e, total_line, &self.blank_3)));
 
         Ok(res)
     }
 }
 
+impl TotalBlock {
+    /// Cross-checks the total, credit and debit fields against the sums of the detail amounts
+    pub async fn validate_detail_sums(
+        &self,
+        line_count: &u32,
+        credit: Cents,
+        debit: Cents,
+    ) -> Vec<Diagnostic> {
+        let (_, amounts) = TotalField::deserialise(&self.total_field).unwrap_or_default();
+        let mut res: Vec<Diagnostic> = Vec::new();
+
+        for (amount_type, value) in [
+            (TotalAmountType::Total, &amounts.total),
+            (TotalAmountType::Credit, &amounts.credit),
+            (TotalAmountType::Debit, &amounts.debit),
+        ] {
+            let _res = validate_detail_sums(self.total_field.clone(), credit, debit, amount_type)
+                .map_err(|e| res.push(Diagnostic::new(&e, line_count + 2u32, value)));
+        }
+
+        res
+    }
+
+    /// Reports the credit and debit sums of the detail amounts that no longer fit the total
+    /// field, None marks a sum that overflowed
+    pub async fn validate_sum_overflow(
+        &self,
+        line_count: &u32,
+        credit: Option<Cents>,
+        debit: Option<Cents>,
+    ) -> Vec<Diagnostic> {
+        let (_, amounts) = TotalField::deserialise(&self.total_field).unwrap_or_default();
+
+        [
+            (credit, LineParseError::TotalCreditOverflow, &amounts.credit),
+            (debit, LineParseError::TotalDebitOverflow, &amounts.debit),
+        ]
+        .into_iter()
+        .filter(|(sum, _, _)| sum.is_none())
+        .map(|(_, e, value)| Diagnostic::new(&e, line_count + 2u32, value))
+        .collect()
+    }
+
+    /// Checks that the net total is zero, as required for self-balancing files
+    pub async fn validate_balanced(&self, line_count: &u32) -> Vec<Diagnostic> {
+        let (_, amounts) = TotalField::deserialise(&self.total_field).unwrap_or_default();
+
+        match validate_balanced(self.total_field.clone()) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![Diagnostic::new(&e, line_count + 2u32, &amounts.total)],
+        }
+    }
+}
+
 impl Display for TotalBlock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -135,13 +178,52 @@ impl Display for TotalBlock {
     }
 }
 
+impl TotalBlock {
+    /// Recomputes the credit, debit and net totals and the record count from the detail lines,
+    /// which must all have a readable amount and transaction code
+    pub async fn from_details(details: &[DetailBlock]) -> Result<Self, CemtexerError> {
+        let mut unreadable: Vec<String> = Vec::new();
+        let mut credit_sum = Some(Cents(0u64));
+        let mut debit_sum = Some(Cents(0u64));
+
+        for (line, detail) in (2u32..).zip(details.iter()) {
+            match detail.checked_credit_debit() {
+                Some((credit, debit)) => {
+                    credit_sum = credit_sum.and_then(|sum| sum.checked_add(credit));
+                    debit_sum = debit_sum.and_then(|sum| sum.checked_add(debit));
+                }
+                None => unreadable.push(format!(
+                    "At line {} the amount `{}` or the transaction code `{}` cannot be read, fix it by hand to recompute the total record",
+                    line, detail.amount, detail.trans_code
+                )),
+            }
+        }
+        if !unreadable.is_empty() {
+            return Err(CemtexerError::Validation(unreadable));
+        }
+
+        let (credit, debit) = match (credit_sum, debit_sum) {
+            (Some(credit), Some(debit)) => (credit, debit),
+            _ => {
+                return Err(CemtexerError::Validation(vec![format!(
+                    "The detail amounts add up to more than the maximum of ${} that fits the total record",
+                    Cents::MAX
+                )]))
+            }
+        };
+        let total_record = TotalRecord::new(details.len().to_string(), credit, debit).await?;
+
+        Ok(Self::from(total_record))
+    }
+}
+
 impl From<TotalRecord> for TotalBlock {
     fn from(tr: TotalRecord) -> Self {
         Self {
             record_type: "7".to_owned(),
             bsb_filler: "999-999".to_owned(),
             blank_1: BLANK_1.to_owned(),
-            total_field: format!("{}{}0000000000", tr.total, tr.total),
+            total_field: format!("{}{}{}", tr.total, tr.credit, tr.debit),
             blank_2: BLANK_2.to_owned(),
             record_count: tr.line_count,
             blank_3: BLANK_3.to_owned(),
@@ -152,6 +234,56 @@ impl From<TotalRecord> for TotalBlock {
 #[test]
 fn test_total_deser() {
     let total: &'static str = "7999-999            000312924700031292470000000000                        000004                                        ";
-    let (_, result) = TotalBlock::deserialise(&total).unwrap();
+    let (_, result) = TotalBlock::deserialise(total).unwrap();
     assert_eq!(result.bsb_filler, "999-999")
 }
+
+#[tokio::test]
+async fn test_total_from_debit_record() {
+    let total_record = TotalRecord::new("2".to_owned(), Cents(1000), Cents(1234))
+        .await
+        .unwrap();
+    let total = TotalBlock::from(total_record);
+    assert_eq!(
+        total.to_string(),
+        "7999-999            000000023400000010000000001234                        000002                                        "
+    );
+    assert!(total
+        .validate_detail_sums(&2u32, Cents(1000), Cents(1234))
+        .await
+        .is_empty());
+    assert_eq!(
+        total
+            .validate_detail_sums(&2u32, Cents(2234), Cents(0))
+            .await
+            .len(),
+        3usize
+    );
+}
+
+#[tokio::test]
+async fn test_total_from_details() {
+    let detail = "1012-002157108231 539000000000S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00000000";
+    let (_, detail) = DetailBlock::deserialise(detail).unwrap();
+    let total = TotalBlock::from_details(std::slice::from_ref(&detail))
+        .await
+        .unwrap();
+    assert_eq!(total.record_count, "000001");
+
+    match TotalBlock::from_details(&[detail.clone(), detail.clone()]).await {
+        Err(CemtexerError::Validation(e)) => assert!(e[0].contains("$99999999.99")),
+        _ => panic!("overflowing sums must be rejected"),
+    }
+
+    let blank = DetailBlock {
+        amount: " ".repeat(10),
+        ..detail.clone()
+    };
+    match TotalBlock::from_details(&[detail, blank]).await {
+        Err(CemtexerError::Validation(e)) => {
+            assert_eq!(e.len(), 1usize);
+            assert!(e[0].starts_with("At line 3 "));
+        }
+        _ => panic!("unreadable amounts must be rejected"),
+    }
+}
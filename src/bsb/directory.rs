@@ -0,0 +1,316 @@
+//! BSB directory loaded from the APCA BSB extract format
+use csv::{ReaderBuilder, WriterBuilder};
+use lazy_static::lazy_static;
+use std::{collections::BTreeMap, fmt::Display, io::Read, path::Path, str::FromStr, sync::Arc};
+
+use crate::errors::*;
+use crate::model::Bsb;
+
+/// Leading BSB digits reserved to the major institutions, used when the directory
+/// does not carry mnemonics, e.g. the embedded list
+const INSTITUTION_PREFIXES: [(&str, &str); 15usize] = [
+    ("01", "ANZ"),
+    ("03", "WBC"),
+    ("73", "WBC"),
+    ("06", "CBA"),
+    ("76", "CBA"),
+    ("08", "NAB"),
+    ("78", "NAB"),
+    ("10", "BSA"),
+    ("11", "STG"),
+    ("33", "STG"),
+    ("12", "BQL"),
+    ("18", "MBL"),
+    ("30", "BWA"),
+    ("48", "MET"),
+    ("55", "BML"),
+];
+
+lazy_static! {
+    static ref EMBEDDED: Arc<BsbDirectory> = Arc::new(
+        include_str!("../data/bsb")
+            .parse()
+            .expect("the embedded BSB list is valid")
+    );
+}
+
+/// Payment systems a branch takes part in, the last column of the APCA extract,
+/// e.g. PEH for paper, electronic and high value
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct PaymentFlags {
+    pub paper: bool,
+    pub electronic: bool,
+    pub high_value: bool,
+}
+
+impl Display for PaymentFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (flag, set) in [
+            ("P", self.paper),
+            ("E", self.electronic),
+            ("H", self.high_value),
+        ] {
+            if set {
+                write!(f, "{}", flag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PaymentFlags {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_uppercase();
+        if let Some(flag) = s.chars().find(|c| !matches!(c, 'P' | 'E' | 'H' | ' ')) {
+            return Err(CemtexerError::Format(format!(
+                "`{}` is not a valid payment system flag, it must be one of P, E or H",
+                flag
+            )));
+        }
+
+        Ok(Self {
+            paper: s.contains('P'),
+            electronic: s.contains('E'),
+            high_value: s.contains('H'),
+        })
+    }
+}
+
+/// Institution owning a BSB, either listed in the directory or only guessed from the prefix
+/// of the BSB when the directory does not carry mnemonics
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Institution {
+    Listed(String),
+    Guessed(&'static str),
+}
+
+impl Institution {
+    pub fn mnemonic(&self) -> &str {
+        match self {
+            Institution::Listed(mnemonic) => mnemonic,
+            Institution::Guessed(mnemonic) => mnemonic,
+        }
+    }
+}
+
+/// A single branch, fields other than the BSB are blank when loaded from a bare list
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct BsbEntry {
+    pub bsb: Bsb,
+    /// Institution mnemonic, the same 3 character code used in the descriptive record
+    pub mnemonic: String,
+    pub branch_name: String,
+    pub address: String,
+    pub suburb: String,
+    pub state: String,
+    pub postcode: String,
+    pub flags: PaymentFlags,
+}
+
+/// BSB directory keyed by BSB for exact lookup
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct BsbDirectory {
+    entries: BTreeMap<String, BsbEntry>,
+}
+
+impl BsbDirectory {
+    /// Reads the APCA BSB extract, i.e. headerless csv rows of BSB, mnemonic, branch name,
+    /// address, suburb, state, postcode and payment flags. Rows with only a BSB are accepted
+    /// and a header row is skipped
+    pub fn from_reader(reader: impl Read) -> Result<Self, CemtexerError> {
+        let entries = read_rows(reader)?
+            .into_iter()
+            .map(|(entry, _)| (entry.bsb.to_string(), entry))
+            .collect();
+
+        Ok(Self { entries })
+    }
+
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self, CemtexerError> {
+        let content = tokio::fs::read(path).await?;
+        Self::from_reader(content.as_slice())
+    }
+
+    /// The list shipped with the crate. It carries BSBs only, branch details and mnemonics
+    /// need an imported APCA extract
+    pub fn embedded() -> Arc<Self> {
+        EMBEDDED.clone()
+    }
+
+    /// Exact lookup, accepts the BSB with or without the hyphen
+    pub fn get(&self, bsb: &str) -> Option<&BsbEntry> {
+        let bsb = parse_bsb(bsb).ok()?;
+        self.entries.get(bsb.as_str())
+    }
+
+    /// Institution owning the BSB, taken from the entry when known and otherwise guessed
+    /// from the institution prefix of the BSB
+    pub fn institution(&self, bsb: &str) -> Option<Institution> {
+        match self.get(bsb) {
+            Some(entry) if !entry.mnemonic.is_empty() => {
+                Some(Institution::Listed(entry.mnemonic.clone()))
+            }
+            _ => institution_by_prefix(bsb).map(Institution::Guessed),
+        }
+    }
+
+    pub fn contains(&self, bsb: &str) -> bool {
+        self.get(bsb).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BsbEntry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl BsbDirectory {
+    pub fn insert(&mut self, entry: BsbEntry) -> Option<BsbEntry> {
+        self.entries.insert(entry.bsb.to_string(), entry)
+    }
+
+    pub fn remove(&mut self, bsb: &str) -> Option<BsbEntry> {
+        let bsb = parse_bsb(bsb).ok()?;
+        self.entries.remove(bsb.as_str())
+    }
+
+    /// Writes the directory back in the APCA extract layout
+    pub fn to_csv(&self) -> Result<String, CemtexerError> {
+        let mut wtr = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+
+        for entry in self.entries.values() {
+            wtr.write_record([
+                entry.bsb.as_str(),
+                &entry.mnemonic,
+                &entry.branch_name,
+                &entry.address,
+                &entry.suburb,
+                &entry.state,
+                &entry.postcode,
+                &entry.flags.to_string(),
+            ])?;
+        }
+
+        let buf = wtr
+            .into_inner()
+            .map_err(|e| CemtexerError::Io(e.into_error()))?;
+        String::from_utf8(buf).map_err(|e| CemtexerError::Format(e.to_string()))
+    }
+}
+
+impl FromStr for BsbDirectory {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+/// Rows of the extract along with the trailing change status column found in monthly deltas
+pub(crate) fn read_rows(reader: impl Read) -> Result<Vec<(BsbEntry, String)>, CemtexerError> {
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let mut rows: Vec<(BsbEntry, String)> = Vec::new();
+
+    for (line, row) in (1u32..).zip(rdr.records()) {
+        let row = row?;
+        let field = |idx: usize| row.get(idx).unwrap_or_default().to_owned();
+        if row.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+
+        let at_line = |e: CemtexerError| match e {
+            CemtexerError::Format(msg) => {
+                CemtexerError::Format(format!("At line {} of the BSB file: {}", line, msg))
+            }
+            e => e,
+        };
+
+        let bsb = match parse_bsb(&field(0usize)) {
+            Ok(bsb) => bsb,
+            Err(_) if line.eq(&1u32) => continue,
+            Err(e) => return Err(at_line(e)),
+        };
+        let flags = field(7usize).parse::<PaymentFlags>().map_err(at_line)?;
+
+        let entry = BsbEntry {
+            bsb,
+            mnemonic: field(1usize).to_uppercase(),
+            branch_name: field(2usize),
+            address: field(3usize),
+            suburb: field(4usize),
+            state: field(5usize).to_uppercase(),
+            postcode: field(6usize),
+            flags,
+        };
+        rows.push((entry, field(8usize).to_uppercase()));
+    }
+
+    Ok(rows)
+}
+
+fn institution_by_prefix(bsb: &str) -> Option<&'static str> {
+    let bsb = parse_bsb(bsb).ok()?;
+    INSTITUTION_PREFIXES
+        .iter()
+        .find(|(prefix, _)| bsb.as_str().starts_with(prefix))
+        .map(|(_, mnemonic)| *mnemonic)
+}
+
+/// Some extracts write the BSB without its hyphen
+fn parse_bsb(i: &str) -> Result<Bsb, CemtexerError> {
+    match i.len() {
+        6usize if i.chars().all(|c| c.is_ascii_digit()) => {
+            format!("{}-{}", &i[..3usize], &i[3usize..]).parse()
+        }
+        _ => i.parse(),
+    }
+}
+
+#[test]
+fn test_bsb_directory() {
+    let extract = "BSB,Mnemonic,Name,Address,Suburb,State,Postcode,Flags\n\
+                   012-002,ANZ,ANZ Smart Choice,115 Pitt Street,Sydney,nsw,2000,PEH\n\
+                   \"062000\",CBA,Sydney,\"48 Martin Place, Ground Floor\",Sydney,NSW,2000,E\n";
+    let directory: BsbDirectory = extract.parse().unwrap();
+    assert_eq!(directory.len(), 2);
+
+    let entry = directory.get("012002").unwrap();
+    assert_eq!(entry.mnemonic, "ANZ");
+    assert_eq!(entry.state, "NSW");
+    assert!(entry.flags.high_value);
+    assert!(!directory.get("062-000").unwrap().flags.paper);
+    assert!(!directory.contains("012-00"));
+    assert!(!directory.contains("012-003"));
+
+    assert!("012-002\n123-45x\n".parse::<BsbDirectory>().is_err());
+    assert_eq!(
+        directory.to_csv().unwrap().parse::<BsbDirectory>().unwrap(),
+        directory
+    );
+    assert_eq!(
+        directory.institution("062-000"),
+        Some(Institution::Listed("CBA".to_owned()))
+    );
+    assert_eq!(
+        BsbDirectory::embedded().institution("083-004"),
+        Some(Institution::Guessed("NAB"))
+    );
+    assert_eq!(BsbDirectory::embedded().institution("999-999"), None);
+    assert!(BsbDirectory::embedded().contains("012-002"));
+    assert!(!BsbDirectory::embedded().contains("12-0020"));
+}
@@ -0,0 +1,256 @@
+//! Imports APCA BSB extracts into the local cache that validation prefers over the embedded list
+use std::{
+    ffi::OsString,
+    fmt::Display,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::bsb::*;
+use crate::errors::*;
+use crate::types::*;
+
+/// Differences between two BSB directories
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct BsbChanges {
+    pub added: Vec<BsbEntry>,
+    pub amended: Vec<BsbEntry>,
+    /// Closed or merged into another BSB
+    pub removed: Vec<BsbEntry>,
+}
+
+impl BsbChanges {
+    pub fn between(old: &BsbDirectory, new: &BsbDirectory) -> Self {
+        let mut changes = Self::default();
+
+        for entry in new.iter() {
+            match old.get(entry.bsb.as_str()) {
+                None => changes.added.push(entry.clone()),
+                Some(previous) if previous.ne(entry) => changes.amended.push(entry.clone()),
+                Some(_) => {}
+            }
+        }
+        changes.removed = old
+            .iter()
+            .filter(|entry| !new.contains(entry.bsb.as_str()))
+            .cloned()
+            .collect();
+
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.amended.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Display for BsbChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} added, {} amended, {} closed or merged",
+            self.added.len(),
+            self.amended.len(),
+            self.removed.len()
+        )?;
+        for (mark, entries) in [
+            ("+", &self.added),
+            ("~", &self.amended),
+            ("-", &self.removed),
+        ] {
+            for entry in entries.iter() {
+                writeln!(f, "{} {}", mark, entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for BsbEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.bsb)?;
+        for value in [
+            &self.mnemonic,
+            &self.branch_name,
+            &self.suburb,
+            &self.state,
+            &self.postcode,
+        ] {
+            if !value.is_empty() {
+                write!(f, " {}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BsbDirectory {
+    /// Applies a monthly delta extract, the change status follows the payment flags column.
+    /// Rows marked C (closed) or M (merged) are removed, N (new), A (amended) or unmarked rows
+    /// are added or replace the existing entry
+    pub fn apply_delta(&mut self, reader: impl Read) -> Result<(), CemtexerError> {
+        for (entry, status) in read_rows(reader)? {
+            match status.as_str() {
+                "C" | "CLOSED" | "M" | "MERGED" => {
+                    self.remove(entry.bsb.as_str());
+                }
+                "" | "N" | "NEW" | "A" | "AMENDED" => {
+                    self.insert(entry);
+                }
+                _ => {
+                    return Err(CemtexerError::Format(format!(
+                    "`{}` is not a valid change status for BSB {}, it must be one of N, A, C or M",
+                    status, entry.bsb
+                )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// $XDG_CACHE_HOME/cemtexer/bsb.csv, falling back to ~/.cache/cemtexer/bsb.csv
+    pub fn cache_path() -> Option<PathBuf> {
+        cache_path_from(std::env::var_os("XDG_CACHE_HOME"), std::env::var_os("HOME"))
+    }
+
+    /// The imported directory, None when nothing has been imported yet
+    pub fn from_cache() -> Result<Option<Self>, CemtexerError> {
+        match Self::cache_path() {
+            Some(path) => Self::read_cache(&path),
+            None => Ok(None),
+        }
+    }
+
+    fn read_cache(path: &Path) -> Result<Option<Self>, CemtexerError> {
+        match path.is_file() {
+            true => Ok(Some(Self::from_reader(std::fs::File::open(path)?)?)),
+            false => Ok(None),
+        }
+    }
+
+    /// Imports an APCA extract into the cache, a delta is applied on top of the cached
+    /// directory or the embedded list when nothing has been imported yet. A full import
+    /// replaces the cache even when it can no longer be read
+    pub async fn import(
+        content: &str,
+        kind: BsbImportKind,
+    ) -> Result<(PathBuf, BsbChanges), CemtexerError> {
+        let path = Self::cache_path().ok_or_else(|| {
+            CemtexerError::Format(
+                "Unable to locate the cache directory, set XDG_CACHE_HOME or HOME".to_owned(),
+            )
+        })?;
+        let changes = Self::import_to(&path, content, kind).await?;
+
+        Ok((path, changes))
+    }
+
+    async fn import_to(
+        path: &Path,
+        content: &str,
+        kind: BsbImportKind,
+    ) -> Result<BsbChanges, CemtexerError> {
+        let (previous, directory) = match kind {
+            BsbImportKind::Full => {
+                // Changes are only listed against a cache that is still readable
+                let previous = Self::read_cache(path).ok().flatten();
+                (previous, content.parse::<Self>()?)
+            }
+            BsbImportKind::Delta => {
+                let previous = Self::read_cache(path)?;
+                let mut directory = previous
+                    .clone()
+                    .unwrap_or_else(|| Self::embedded().as_ref().clone());
+                directory.apply_delta(content.as_bytes())?;
+                (previous, directory)
+            }
+        };
+        if directory.is_empty() {
+            return Err(CemtexerError::Format(
+                "The BSB file does not contain any BSB".to_owned(),
+            ));
+        }
+
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::write(path, directory.to_csv()?).await?;
+
+        let previous = previous.unwrap_or_else(|| Self::embedded().as_ref().clone());
+        Ok(BsbChanges::between(&previous, &directory))
+    }
+}
+
+fn cache_path_from(xdg_cache_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    let dir = match (xdg_cache_home, home) {
+        (Some(cache), _) if !cache.is_empty() => PathBuf::from(cache),
+        (_, Some(home)) if !home.is_empty() => PathBuf::from(home).join(".cache"),
+        _ => return None,
+    };
+
+    Some(dir.join("cemtexer").join("bsb.csv"))
+}
+
+#[test]
+fn test_apply_delta() {
+    let old: BsbDirectory =
+        "012-002,ANZ,Sydney,,,NSW,2000,PEH\n012-003,ANZ,Parramatta,,,NSW,2150,PEH\n"
+            .parse()
+            .unwrap();
+    let mut new = old.clone();
+    new.apply_delta(
+        "012-003,ANZ,Parramatta,,,NSW,2150,PEH,C\n\
+         012-002,ANZ,Sydney CBD,,,NSW,2000,PEH,A\n\
+         062-000,CBA,Sydney,,,NSW,2000,PEH,N\n"
+            .as_bytes(),
+    )
+    .unwrap();
+
+    let changes = BsbChanges::between(&old, &new);
+    assert_eq!(changes.added.len(), 1);
+    assert_eq!(changes.amended[0].branch_name, "Sydney CBD");
+    assert_eq!(changes.removed[0].bsb.as_str(), "012-003");
+    assert!(changes
+        .to_string()
+        .starts_with("1 added, 1 amended, 1 closed or merged\n+ 062-000 CBA Sydney NSW 2000\n"));
+    assert!(new.apply_delta("012-002,,,,,,,,X\n".as_bytes()).is_err());
+}
+
+#[test]
+fn test_cache_path() {
+    assert_eq!(
+        cache_path_from(Some("/tmp/cache".into()), Some("/home/me".into())),
+        Some(PathBuf::from("/tmp/cache/cemtexer/bsb.csv"))
+    );
+    assert_eq!(
+        cache_path_from(Some("".into()), Some("/home/me".into())),
+        Some(PathBuf::from("/home/me/.cache/cemtexer/bsb.csv"))
+    );
+    assert_eq!(cache_path_from(None, None), None);
+}
+
+#[tokio::test]
+async fn test_import_over_corrupt_cache() {
+    let path = std::env::temp_dir().join("cemtexer_test_import_over_corrupt_cache.csv");
+    let corrupt = "012-002,ANZ\nbogus-line\n";
+    tokio::fs::write(&path, corrupt).await.unwrap();
+    assert!(BsbDirectory::read_cache(&path).is_err());
+    assert!(
+        BsbDirectory::import_to(&path, "062-000,CBA,,,,,,,N\n", BsbImportKind::Delta)
+            .await
+            .is_err()
+    );
+
+    BsbDirectory::import_to(
+        &path,
+        "062-000,CBA,Sydney,,,NSW,2000,PEH\n",
+        BsbImportKind::Full,
+    )
+    .await
+    .unwrap();
+    let cached = BsbDirectory::read_cache(&path).unwrap().unwrap();
+    let _ = tokio::fs::remove_file(&path).await;
+    assert_eq!(cached.len(), 1usize);
+    assert_eq!(cached.get("062000").unwrap().mnemonic, "CBA");
+}
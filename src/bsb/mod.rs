@@ -0,0 +1,6 @@
+//! Module for the BSB directory used by BSB validation
+mod directory;
+mod import;
+
+pub use self::directory::*;
+pub use self::import::*;
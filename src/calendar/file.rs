@@ -0,0 +1,175 @@
+//! Reads holidays from ICS calendars and CSV files, such as the Australian public
+//! holidays dataset on data.gov.au
+use csv::ReaderBuilder;
+use std::{io::Read, path::Path};
+use time::{Date, Month};
+
+use crate::calendar::*;
+use crate::errors::*;
+use crate::types::*;
+
+impl Holiday {
+    /// ICS calendars or CSV files, told apart by the `BEGIN:VCALENDAR` line
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Vec<Holiday>, CemtexerError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        match content
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with("BEGIN:VCALENDAR")
+        {
+            true => Self::from_ics(&content),
+            false => Self::from_csv(content.as_bytes()),
+        }
+    }
+
+    /// All-day events, the SUMMARY being the holiday name
+    pub fn from_ics(content: &str) -> Result<Vec<Holiday>, CemtexerError> {
+        let mut res: Vec<Holiday> = Vec::new();
+        let mut start: Option<Date> = None;
+        let mut name = String::new();
+
+        for line in unfold(content).iter() {
+            let (property, value) = line.split_once(':').unwrap_or((line.as_str(), ""));
+            let property = property.split(';').next().unwrap_or_default();
+            match property.to_uppercase().as_str() {
+                "BEGIN" if value.eq_ignore_ascii_case("VEVENT") => {
+                    start = None;
+                    name.clear();
+                }
+                "DTSTART" => {
+                    start = Some(parse_date(value.get(..8usize).unwrap_or(value)).ok_or_else(
+                        || {
+                            CemtexerError::Format(format!(
+                                "`{}` is not a valid date in the holiday calendar",
+                                value
+                            ))
+                        },
+                    )?)
+                }
+                "SUMMARY" => {
+                    name = value
+                        .replace("\\,", ",")
+                        .replace("\\;", ";")
+                        .replace("\\\\", "\\")
+                }
+                "END" if value.eq_ignore_ascii_case("VEVENT") => {
+                    if let Some(date) = start.take() {
+                        res.push(Holiday {
+                            date,
+                            name: name.clone(),
+                            state: None,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Rows of date, name and optionally the jurisdiction in the last column,
+    /// dates are either YYYYMMDD or YYYY-MM-DD and a header row is skipped
+    pub fn from_csv(reader: impl Read) -> Result<Vec<Holiday>, CemtexerError> {
+        let mut rdr = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut res: Vec<Holiday> = Vec::new();
+
+        for (line, row) in (1u32..).zip(rdr.records()) {
+            let row = row?;
+            if row.iter().all(|value| value.is_empty()) {
+                continue;
+            }
+
+            let at_line = |msg: String| {
+                CemtexerError::Format(format!("At line {} of the holiday file: {}", line, msg))
+            };
+
+            let value = row.get(0usize).unwrap_or_default();
+            let date = match parse_date(value) {
+                Some(date) => date,
+                None if line.eq(&1u32) => continue,
+                None => return Err(at_line(format!("`{}` is not a valid date", value))),
+            };
+            let state = match row.len() {
+                0..=2 => None,
+                len => match row.get(len - 1usize).unwrap_or_default().to_uppercase() {
+                    jurisdiction if jurisdiction.is_empty() => None,
+                    jurisdiction if jurisdiction.eq("NAT") || jurisdiction.eq("NATIONAL") => None,
+                    jurisdiction => Some(jurisdiction.parse::<AuState>().map_err(|_| {
+                        at_line(format!("`{}` is not a valid state", jurisdiction))
+                    })?),
+                },
+            };
+
+            res.push(Holiday {
+                date,
+                name: row.get(1usize).unwrap_or_default().to_owned(),
+                state,
+            });
+        }
+
+        Ok(res)
+    }
+}
+
+/// Joins the continuation lines of an ICS file, which start with a blank
+fn unfold(content: &str) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+
+    for line in content.trim_start_matches('\u{feff}').lines() {
+        match (
+            line.strip_prefix(' ').or(line.strip_prefix('\t')),
+            res.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => res.push(line.to_owned()),
+        }
+    }
+
+    res
+}
+
+fn parse_date(i: &str) -> Option<Date> {
+    let i = i.replace('-', "");
+    if !i.len().eq(&8usize) || !i.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let year = i[0..4].parse::<i32>().ok()?;
+    let month = Month::try_from(i[4..6].parse::<u8>().ok()?).ok()?;
+    let day = i[6..8].parse::<u8>().ok()?;
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+#[test]
+fn test_holiday_files() {
+    let ics = "BEGIN:VCALENDAR\r\n\
+               BEGIN:VEVENT\r\n\
+               DTSTART;VALUE=DATE:20240927\r\n\
+               SUMMARY:Friday before the AFL\r\n  Grand Final\r\n\
+               END:VEVENT\r\n\
+               END:VCALENDAR\r\n";
+    let holidays = Holiday::from_ics(ics).unwrap();
+    assert_eq!(holidays.len(), 1);
+    assert_eq!(holidays[0].name, "Friday before the AFL Grand Final");
+    assert_eq!(holidays[0].date, parse_date("2024-09-27").unwrap());
+
+    let csv = "Date,Holiday Name,Information,More Information,Jurisdiction\n\
+               20240927,Friday before the AFL Grand Final,,,vic\n\
+               2024-12-31,Bank closure\n";
+    let holidays = Holiday::from_csv(csv.as_bytes()).unwrap();
+    assert_eq!(holidays[0].state, Some(AuState::Vic));
+    assert_eq!(holidays[1].state, None);
+    assert!(Holiday::from_csv("20240927,Holiday\n2024-13-01,Holiday\n".as_bytes()).is_err());
+
+    let mut calendar = HolidayCalendar::new(Some(AuState::Vic));
+    calendar.extend(holidays);
+    assert!(!calendar.is_business_day(parse_date("20240927").unwrap()));
+    assert!(calendar
+        .with_state(Some(AuState::Nsw))
+        .is_business_day(parse_date("20240927").unwrap()));
+}
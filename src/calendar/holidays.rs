@@ -0,0 +1,268 @@
+//! Australian public holidays and the business days payments are processed on
+use std::collections::BTreeMap;
+use time::{Date, Duration, Month, Weekday};
+
+use crate::types::*;
+
+/// A holiday read from a calendar file, without a state it applies everywhere
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Holiday {
+    pub date: Date,
+    pub name: String,
+    pub state: Option<AuState>,
+}
+
+/// National public holidays plus those of `state`, with substitute days for holidays
+/// falling on a weekend. Holidays proclaimed at short notice, e.g. the AFL Grand Final
+/// Friday, have to be loaded from a calendar file
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct HolidayCalendar {
+    state: Option<AuState>,
+    loaded: Vec<Holiday>,
+}
+
+impl HolidayCalendar {
+    pub fn new(state: Option<AuState>) -> Self {
+        Self {
+            state,
+            loaded: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> Option<AuState> {
+        self.state
+    }
+
+    /// Same loaded holidays for another state
+    pub fn with_state(&self, state: Option<AuState>) -> Self {
+        Self {
+            state,
+            ..self.clone()
+        }
+    }
+
+    /// Adds holidays on top of the computed ones
+    pub fn extend(&mut self, holidays: impl IntoIterator<Item = Holiday>) {
+        self.loaded.extend(holidays);
+    }
+
+    /// Holidays observed in the year, keyed by the day they are observed on
+    pub fn holidays(&self, year: i32) -> BTreeMap<Date, String> {
+        let mut res: BTreeMap<Date, String> = BTreeMap::new();
+        let mut add = |date: Date, name: &str| {
+            res.entry(date).or_insert_with(|| name.to_owned());
+        };
+
+        let easter = easter_sunday(year);
+        add(
+            substitute(date(year, Month::January, 1u8)),
+            "New Year's Day",
+        );
+        add(
+            substitute(date(year, Month::January, 26u8)),
+            "Australia Day",
+        );
+        add(easter - Duration::days(2i64), "Good Friday");
+        add(easter + Duration::days(1i64), "Easter Monday");
+        add(date(year, Month::April, 25u8), "Anzac Day");
+        add(
+            boxing_substitute(date(year, Month::December, 25u8)),
+            "Christmas Day",
+        );
+        add(
+            boxing_substitute(date(year, Month::December, 26u8)),
+            "Boxing Day",
+        );
+
+        match self.state {
+            Some(AuState::Act) => {
+                add(nth_monday(year, Month::March, 2u8), "Canberra Day");
+                add(reconciliation_day(year), "Reconciliation Day");
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+                add(nth_monday(year, Month::October, 1u8), "Labour Day");
+            }
+            Some(AuState::Nsw) => {
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+                add(nth_monday(year, Month::August, 1u8), "Bank Holiday");
+                add(nth_monday(year, Month::October, 1u8), "Labour Day");
+            }
+            Some(AuState::Nt) => {
+                add(nth_monday(year, Month::May, 1u8), "May Day");
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+                add(nth_monday(year, Month::August, 1u8), "Picnic Day");
+            }
+            Some(AuState::Qld) => {
+                add(nth_monday(year, Month::May, 1u8), "Labour Day");
+                add(nth_monday(year, Month::October, 1u8), "King's Birthday");
+            }
+            Some(AuState::Sa) => {
+                add(nth_monday(year, Month::March, 2u8), "Adelaide Cup Day");
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+                add(nth_monday(year, Month::October, 1u8), "Labour Day");
+            }
+            Some(AuState::Tas) => {
+                add(nth_monday(year, Month::March, 2u8), "Eight Hours Day");
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+            }
+            Some(AuState::Vic) => {
+                add(nth_monday(year, Month::March, 2u8), "Labour Day");
+                add(nth_monday(year, Month::June, 2u8), "King's Birthday");
+                add(
+                    date(year, Month::October, 31u8).next_occurrence(Weekday::Tuesday),
+                    "Melbourne Cup Day",
+                );
+            }
+            Some(AuState::Wa) => {
+                add(substitute(date(year, Month::April, 25u8)), "Anzac Day");
+                add(nth_monday(year, Month::March, 1u8), "Labour Day");
+                add(nth_monday(year, Month::June, 1u8), "Western Australia Day");
+                // Proclaimed yearly, usually on the last Monday of September
+                add(last_monday(year, Month::September), "King's Birthday");
+            }
+            None => {}
+        }
+
+        for holiday in self.loaded.iter() {
+            if holiday.date.year().eq(&year)
+                && (holiday.state.is_none() || holiday.state.eq(&self.state))
+            {
+                add(holiday.date, &holiday.name);
+            }
+        }
+
+        res
+    }
+
+    pub fn holiday(&self, date: Date) -> Option<String> {
+        self.holidays(date.year()).remove(&date)
+    }
+
+    pub fn is_business_day(&self, date: Date) -> bool {
+        self.closed_reason(date).is_none()
+    }
+
+    /// Why no payments are processed on the date, e.g. "a Saturday" or "Christmas Day"
+    pub fn closed_reason(&self, date: Date) -> Option<String> {
+        match date.weekday() {
+            Weekday::Saturday | Weekday::Sunday => Some(format!("a {}", date.weekday())),
+            _ => self.holiday(date),
+        }
+    }
+
+    /// The date itself when it is a business day, otherwise the next one
+    pub fn next_business_day(&self, date: Date) -> Date {
+        let mut date = date;
+        while !self.is_business_day(date) {
+            date = date.next_day().unwrap_or(date);
+        }
+        date
+    }
+}
+
+fn date(year: i32, month: Month, day: u8) -> Date {
+    Date::from_calendar_date(year, month, day).expect("holiday rules only use valid dates")
+}
+
+/// Holidays falling on a weekend are observed on the following Monday
+fn substitute(date: Date) -> Date {
+    match date.weekday() {
+        Weekday::Saturday => date + Duration::days(2i64),
+        Weekday::Sunday => date + Duration::days(1i64),
+        _ => date,
+    }
+}
+
+/// Christmas and Boxing Day on a weekend move two days so they never share a substitute day
+fn boxing_substitute(date: Date) -> Date {
+    match date.weekday() {
+        Weekday::Saturday | Weekday::Sunday => date + Duration::days(2i64),
+        _ => date,
+    }
+}
+
+/// The `n`th Monday of the month
+fn nth_monday(year: i32, month: Month, n: u8) -> Date {
+    let before = date(year, month, 1u8) - Duration::days(1i64);
+    before.next_occurrence(Weekday::Monday) + Duration::weeks(i64::from(n - 1u8))
+}
+
+fn last_monday(year: i32, month: Month) -> Date {
+    let last = date(year, month, month.length(year));
+    last - Duration::days(i64::from(last.weekday().number_days_from_monday()))
+}
+
+/// Monday on or after 27 May
+fn reconciliation_day(year: i32) -> Date {
+    date(year, Month::May, 26u8).next_occurrence(Weekday::Monday)
+}
+
+/// Anonymous Gregorian algorithm
+fn easter_sunday(year: i32) -> Date {
+    let a = year % 19i32;
+    let b = year / 100i32;
+    let c = year % 100i32;
+    let d = b / 4i32;
+    let e = b % 4i32;
+    let f = (b + 8i32) / 25i32;
+    let g = (b - f + 1i32) / 3i32;
+    let h = (19i32 * a + b - d - g + 15i32) % 30i32;
+    let i = c / 4i32;
+    let k = c % 4i32;
+    let l = (32i32 + 2i32 * e + 2i32 * i - h - k) % 7i32;
+    let m = (a + 11i32 * h + 22i32 * l) / 451i32;
+    let month = (h + l - 7i32 * m + 114i32) / 31i32;
+    let day = (h + l - 7i32 * m + 114i32) % 31i32 + 1i32;
+
+    date(
+        year,
+        Month::try_from(month as u8).expect("Easter falls in March or April"),
+        day as u8,
+    )
+}
+
+#[test]
+fn test_holidays() {
+    let national = HolidayCalendar::default();
+    assert_eq!(easter_sunday(2024i32), date(2024i32, Month::March, 31u8));
+    assert_eq!(easter_sunday(2025i32), date(2025i32, Month::April, 20u8));
+    assert_eq!(
+        national
+            .holiday(date(2024i32, Month::March, 29u8))
+            .as_deref(),
+        Some("Good Friday")
+    );
+    // Christmas on Saturday and Boxing Day on Sunday in 2021
+    assert!(national
+        .holiday(date(2021i32, Month::December, 27u8))
+        .is_some());
+    assert!(national
+        .holiday(date(2021i32, Month::December, 28u8))
+        .is_some());
+    assert_eq!(
+        national
+            .closed_reason(date(2024i32, Month::October, 5u8))
+            .as_deref(),
+        Some("a Saturday")
+    );
+    assert!(national.is_business_day(date(2024i32, Month::October, 7u8)));
+
+    let nsw = national.with_state(Some(AuState::Nsw));
+    assert_eq!(
+        nsw.closed_reason(date(2024i32, Month::October, 7u8))
+            .as_deref(),
+        Some("Labour Day")
+    );
+    assert_eq!(
+        nsw.next_business_day(date(2024i32, Month::October, 5u8)),
+        date(2024i32, Month::October, 8u8)
+    );
+    let vic = national.with_state(Some(AuState::Vic));
+    assert_eq!(
+        vic.holiday(date(2024i32, Month::November, 5u8)).as_deref(),
+        Some("Melbourne Cup Day")
+    );
+    let wa = national.with_state(Some(AuState::Wa));
+    assert!(wa.holiday(date(2025i32, Month::September, 29u8)).is_some());
+    let act = national.with_state(Some(AuState::Act));
+    assert!(act.holiday(date(2024i32, Month::May, 27u8)).is_some());
+}
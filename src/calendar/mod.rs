@@ -0,0 +1,9 @@
+//! Module for the business-day calendar used by settlement date validation
+mod file;
+mod holidays;
+mod relative;
+mod sydney;
+
+pub use self::holidays::*;
+pub use self::relative::*;
+pub use self::sydney::*;
@@ -0,0 +1,98 @@
+//! Settlement dates relative to the day the file is generated
+use std::{fmt::Display, str::FromStr};
+use time::{Date, Duration};
+
+use crate::calendar::*;
+use crate::errors::*;
+
+/// Expressions accepted in place of a DDMMYY settlement date, i.e. `today`, `tomorrow`,
+/// `next-business-day`, `+2d` for calendar days or `+2bd` for business days
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum RelativeDate {
+    Today,
+    Tomorrow,
+    NextBusinessDay,
+    Days(u32),
+    BusinessDays(u32),
+}
+
+impl RelativeDate {
+    pub fn resolve(&self, today: Date, calendar: &HolidayCalendar) -> Date {
+        let next_day = |date: Date| date.next_day().unwrap_or(date);
+
+        match self {
+            RelativeDate::Today => today,
+            RelativeDate::Tomorrow => next_day(today),
+            RelativeDate::NextBusinessDay => calendar.next_business_day(next_day(today)),
+            RelativeDate::Days(days) => today + Duration::days(i64::from(*days)),
+            RelativeDate::BusinessDays(days) => {
+                let mut date = calendar.next_business_day(today);
+                for _ in 0u32..*days {
+                    date = calendar.next_business_day(next_day(date));
+                }
+                date
+            }
+        }
+    }
+}
+
+impl FromStr for RelativeDate {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim().to_lowercase();
+        let err = || {
+            CemtexerError::Template(format!(
+                "`{}` is not a valid settlement date, it must be either DDMMYY, today, tomorrow, next-business-day, +Nd or +Nbd",
+                s
+            ))
+        };
+        let days = |n: &str| n.parse::<u32>().map_err(|_| err());
+
+        match expression.as_str() {
+            "today" => Ok(RelativeDate::Today),
+            "tomorrow" => Ok(RelativeDate::Tomorrow),
+            "next-business-day" => Ok(RelativeDate::NextBusinessDay),
+            _ => match expression.strip_prefix('+') {
+                Some(n) if n.ends_with("bd") => {
+                    Ok(RelativeDate::BusinessDays(days(n.trim_end_matches("bd"))?))
+                }
+                Some(n) if n.ends_with('d') => {
+                    Ok(RelativeDate::Days(days(n.trim_end_matches('d'))?))
+                }
+                _ => Err(err()),
+            },
+        }
+    }
+}
+
+impl Display for RelativeDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelativeDate::Today => write!(f, "today"),
+            RelativeDate::Tomorrow => write!(f, "tomorrow"),
+            RelativeDate::NextBusinessDay => write!(f, "next-business-day"),
+            RelativeDate::Days(days) => write!(f, "+{}d", days),
+            RelativeDate::BusinessDays(days) => write!(f, "+{}bd", days),
+        }
+    }
+}
+
+#[test]
+fn test_relative_date() {
+    use time::macros::date;
+
+    let calendar = HolidayCalendar::default();
+    // Thursday before Easter 2024
+    let today = date!(2024 - 03 - 28);
+    let resolve = |s: &str| s.parse::<RelativeDate>().unwrap().resolve(today, &calendar);
+    assert_eq!(resolve("today"), today);
+    assert_eq!(resolve("Tomorrow"), date!(2024 - 03 - 29));
+    assert_eq!(resolve("next-business-day"), date!(2024 - 04 - 02));
+    assert_eq!(resolve("+0bd"), today);
+    assert_eq!(resolve("+2bd"), date!(2024 - 04 - 03));
+    assert_eq!(resolve("+5d"), date!(2024 - 04 - 02));
+    assert_eq!("+2BD".parse::<RelativeDate>().unwrap().to_string(), "+2bd");
+    assert!("300422".parse::<RelativeDate>().is_err());
+    assert!("+xbd".parse::<RelativeDate>().is_err());
+}
@@ -0,0 +1,83 @@
+//! Local time in Sydney, where settlement dates are counted from
+use time::{Date, Duration, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset, Weekday};
+
+/// AEST, UTC+10
+const STANDARD: i8 = 10i8;
+/// AEDT, UTC+11
+const DAYLIGHT: i8 = 11i8;
+
+/// Offset from UTC in Sydney at the instant. Daylight saving starts at 2am AEST on the first
+/// Sunday of October and ends at 3am AEDT on the first Sunday of April, both 4pm UTC on the
+/// Saturday before
+pub fn sydney_offset(utc: OffsetDateTime) -> UtcOffset {
+    let utc = utc.to_offset(UtcOffset::UTC);
+    let year = utc.year();
+    let ends = transition(year, Month::April);
+    let starts = transition(year, Month::October);
+
+    let hours = match utc.lt(&ends) || utc.ge(&starts) {
+        true => DAYLIGHT,
+        false => STANDARD,
+    };
+    UtcOffset::from_hms(hours, 0i8, 0i8).expect("Sydney offsets are whole hours")
+}
+
+/// The instant in Sydney local time
+pub fn sydney_time(utc: OffsetDateTime) -> OffsetDateTime {
+    utc.to_offset(sydney_offset(utc))
+}
+
+/// Today's date in Sydney
+pub fn today() -> Date {
+    sydney_time(OffsetDateTime::now_utc()).date()
+}
+
+/// 4pm UTC on the Saturday before the first Sunday of the month
+fn transition(year: i32, month: Month) -> OffsetDateTime {
+    let first_sunday = Date::from_calendar_date(year, month, 1u8)
+        .expect("the first of the month is a valid date")
+        .previous_day()
+        .map(|before| before.next_occurrence(Weekday::Sunday))
+        .expect("the last day of the previous month is a valid date");
+
+    PrimitiveDateTime::new(
+        first_sunday - Duration::days(1i64),
+        Time::from_hms(16u8, 0u8, 0u8).expect("4pm is a valid time"),
+    )
+    .assume_utc()
+}
+
+#[test]
+fn test_sydney_time() {
+    use time::macros::datetime;
+
+    // 2024 daylight saving ended on 7 April and started on 6 October
+    assert_eq!(
+        sydney_offset(datetime!(2024-04-06 15:59 UTC)).whole_hours(),
+        11i8
+    );
+    assert_eq!(
+        sydney_offset(datetime!(2024-04-06 16:00 UTC)).whole_hours(),
+        10i8
+    );
+    assert_eq!(
+        sydney_offset(datetime!(2024-10-05 15:59 UTC)).whole_hours(),
+        10i8
+    );
+    assert_eq!(
+        sydney_offset(datetime!(2024-10-05 16:00 UTC)).whole_hours(),
+        11i8
+    );
+    assert_eq!(
+        sydney_time(datetime!(2024-06-30 14:30 UTC)).date(),
+        time::macros::date!(2024 - 07 - 01)
+    );
+    assert_eq!(
+        sydney_time(datetime!(2024-12-31 12:59 UTC)).date(),
+        time::macros::date!(2024 - 12 - 31)
+    );
+    assert_eq!(
+        sydney_time(datetime!(2024-12-31 13:00 UTC)).date(),
+        time::macros::date!(2025 - 01 - 01)
+    );
+}
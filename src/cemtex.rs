@@ -1,12 +1,15 @@
 //! Various struct for reading .aba file format
-use std::{fmt::Display, path::Path, process::exit};
-use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-};
+use std::{collections::BTreeMap, fmt::Display, io::BufRead, path::Path, str::FromStr};
 
 use crate::blocks::*;
+use crate::context::*;
+use crate::csv::*;
 use crate::errors::*;
+use crate::helper::CenturyWindow;
+use crate::model::{AbaFile, Cents, Descriptive, Detail, Total};
+use crate::parser_utils::non_ascii_chars;
+use crate::report::*;
+use crate::types::*;
 
 /// Cemtex representation
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -15,14 +18,173 @@ pub struct Cemtex {
 }
 
 impl Cemtex {
-    pub async fn new(path: impl AsRef<Path>) -> Cemtex {
-        Self {
-            inner: CemtexInner::new(path).await,
+    /// Reads .aba content from a file path
+    pub async fn new(path: impl AsRef<Path>) -> Result<Cemtex, CemtexerError> {
+        Self::new_with_mode(path, ParseMode::Strict).await
+    }
+
+    /// Reads .aba content from a file path, in lenient mode mis-sized lines are
+    /// reported by `validate` instead of rejected
+    pub async fn new_with_mode(
+        path: impl AsRef<Path>,
+        mode: ParseMode,
+    ) -> Result<Cemtex, CemtexerError> {
+        Ok(Self {
+            inner: CemtexInner::new(path, mode).await?,
+        })
+    }
+
+    /// Reads .aba content from any buffered reader, e.g. a request body or a byte slice
+    pub fn from_reader(reader: impl BufRead) -> Result<Cemtex, CemtexerError> {
+        Self::from_reader_with_mode(reader, ParseMode::Strict)
+    }
+
+    pub fn from_reader_with_mode(
+        reader: impl BufRead,
+        mode: ParseMode,
+    ) -> Result<Cemtex, CemtexerError> {
+        Ok(Self {
+            inner: CemtexInner::from_reader(reader, mode)?,
+        })
+    }
+
+    pub fn from_str_with_mode(s: &str, mode: ParseMode) -> Result<Cemtex, CemtexerError> {
+        Ok(Self {
+            inner: CemtexInner::from_str_with_mode(s, mode)?,
+        })
+    }
+
+    /// Reads .aba content from raw bytes, which must be valid UTF-8
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cemtex, CemtexerError> {
+        let content = std::str::from_utf8(bytes)
+            .map_err(|e| CemtexerError::Format(format!("The content is not valid UTF-8: {}", e)))?;
+        content.parse()
+    }
+
+    /// Validates the content in memory against the context and collects every diagnostic
+    /// into a report
+    pub async fn validate(
+        &self,
+        ctx: &ValidationContext,
+    ) -> Result<ValidationReport, CemtexerError> {
+        CemtexInner::validate_inner(&self.inner, ctx).await
+    }
+
+    /// Validates the content and writes the report rendered in `format` to `path`,
+    /// `source` names the validated file inside the rendered report
+    pub async fn write_report(
+        &self,
+        path: impl AsRef<Path>,
+        format: ReportFormat,
+        source: &str,
+        ctx: &ValidationContext,
+    ) -> Result<ValidationReport, CemtexerError> {
+        let report = self.validate(ctx).await?;
+        report.write(path, format, source).await?;
+
+        Ok(report)
+    }
+
+    /// Checks that the net total is zero, as required for self-balancing files
+    pub async fn validate_balanced(&self) -> Result<Vec<Diagnostic>, CemtexerError> {
+        let (_, block_total) = TotalBlock::deserialise(&self.inner.total)
+            .map_err(|e| CemtexerError::Format(e.to_string()))?;
+
+        Ok(TotalBlock::validate_balanced(&block_total, &(self.inner.line_count - 2u32)).await)
+    }
+
+    /// Converts the detail lines into csv rows in the layout that `CsvRecord::read` accepts,
+    /// the transaction code, indicator and trace columns are always written so nothing is lost
+    pub fn to_csv(&self) -> Result<String, CemtexerError> {
+        let records: Vec<CsvRecord> = self
+            .inner
+            .typed_details()?
+            .iter()
+            .map(CsvRecord::from)
+            .collect();
+
+        CsvRecord::write_string(&records)
+    }
+
+    /// Recovers the template settings that generate the same descriptive record
+    pub fn to_settings(&self) -> Result<SettlementSettings, CemtexerError> {
+        self.to_settings_in(&CenturyWindow::default())
+    }
+
+    /// Same as `to_settings` with the two-digit settlement year in `window`
+    pub fn to_settings_in(
+        &self,
+        window: &CenturyWindow,
+    ) -> Result<SettlementSettings, CemtexerError> {
+        let file = self.to_aba_file_in(window)?;
+
+        Ok(SettlementSettings::from_records(
+            &file.descriptive,
+            file.details.first(),
+        ))
+    }
+
+    /// Converts the whole file into its typed form, e.g. for the json exchange format
+    pub fn to_aba_file(&self) -> Result<AbaFile, CemtexerError> {
+        self.to_aba_file_in(&CenturyWindow::default())
+    }
+
+    /// Same as `to_aba_file` with the two-digit settlement year in `window`
+    pub fn to_aba_file_in(&self, window: &CenturyWindow) -> Result<AbaFile, CemtexerError> {
+        let (_, block_desc) = DescriptiveBlock::deserialise(&self.inner.descriptive)
+            .map_err(|e| CemtexerError::Format(format!("At line 1: {}", e)))?;
+        let (_, block_total) = TotalBlock::deserialise(&self.inner.total).map_err(|e| {
+            CemtexerError::Format(format!("At line {}: {}", self.inner.line_count, e))
+        })?;
+
+        Ok(AbaFile {
+            descriptive: Descriptive::from_block_in(&block_desc, window)?,
+            details: self.inner.typed_details()?,
+            total: Total::try_from(&block_total)?,
+        })
+    }
+
+    /// Rewrites every line with correct padding, justification and case, and recomputes the
+    /// total record from the detail lines. Fails without fixing anything when a line cannot be
+    /// split into fields or a detail amount or transaction code cannot be read
+    pub async fn fix(&self) -> Result<Vec<String>, CemtexerError> {
+        let mut lines: Vec<String> = Vec::new();
+
+        let (_, descriptive) = DescriptiveBlock::deserialise(&self.inner.descriptive)
+            .map_err(|e| CemtexerError::Format(format!("At line 1: {}", e)))?;
+        lines.push(descriptive.fix().to_string());
+
+        let mut details: Vec<DetailBlock> = Vec::new();
+        for (line, detail) in (2u32..).zip(self.inner.detail.iter()) {
+            let (_, block) = DetailBlock::deserialise(detail)
+                .map_err(|e| CemtexerError::Format(format!("At line {}: {}", line, e)))?;
+            details.push(block.fix());
         }
+        lines.extend(details.iter().map(|detail| detail.to_string()));
+
+        lines.push(TotalBlock::from_details(&details).await?.to_string());
+
+        Ok(lines)
+    }
+
+    /// Serialises the whole file as json
+    pub fn to_json(&self) -> Result<String, CemtexerError> {
+        self.to_json_in(&CenturyWindow::default())
+    }
+
+    /// Same as `to_json` with the two-digit settlement year in `window`
+    pub fn to_json_in(&self, window: &CenturyWindow) -> Result<String, CemtexerError> {
+        self.to_aba_file_in(window)?.to_json()
     }
+}
+
+impl FromStr for Cemtex {
+    type Err = CemtexerError;
 
-    pub async fn validate(&self, path: impl AsRef<Path> + Display) {
-        let _ = CemtexInner::validate_inner(&self.inner, path).await;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            inner: CemtexInner::from_str(s)?,
+        })
     }
 }
 
@@ -33,116 +195,523 @@ pub struct CemtexInner {
     pub detail: Vec<String>,
     pub total: String,
     pub line_count: u32,
+    /// Lines padded or truncated to 120 characters in lenient mode
+    pub length_errors: Vec<Diagnostic>,
 }
 
 impl CemtexInner {
-    pub async fn new(path: impl AsRef<Path>) -> CemtexInner {
-        let file = match File::open(path).await {
-            Ok(file) => file,
-            Err(_) => {
-                println!("Cannot locate file, program aborting");
-                exit(1)
+    pub async fn new(
+        path: impl AsRef<Path>,
+        mode: ParseMode,
+    ) -> Result<CemtexInner, CemtexerError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        Self::from_str_with_mode(&content, mode)
+    }
+
+    pub fn from_reader(
+        reader: impl BufRead,
+        mode: ParseMode,
+    ) -> Result<CemtexInner, CemtexerError> {
+        let lines = reader.lines().collect::<Result<Vec<String>, _>>()?;
+        Self::from_lines(lines, mode)
+    }
+
+    pub fn from_str_with_mode(s: &str, mode: ParseMode) -> Result<CemtexInner, CemtexerError> {
+        Self::from_lines(s.lines().map(|line| line.to_owned()).collect(), mode)
+    }
+
+    /// Line breaks are stripped by the callers, CRLF included
+    fn from_lines(mut lines: Vec<String>, mode: ParseMode) -> Result<CemtexInner, CemtexerError> {
+        if let Some(first) = lines.first_mut() {
+            if let Some(stripped) = first.strip_prefix('\u{feff}') {
+                *first = stripped.to_owned();
             }
-        };
-        let buf = BufReader::new(file);
+        }
 
         let mut line_count: u32 = 0u32;
-        let mut err_count: u32 = 0u32;
         let mut err: Vec<String> = Vec::new();
-        let entries = read_buf(buf, &mut line_count, &mut err_count, &mut err).await;
+        let mut length_errors: Vec<Diagnostic> = Vec::new();
+        let entries = match mode {
+            ParseMode::Strict => read_buf(lines, &mut line_count, &mut err),
+            ParseMode::Lenient => {
+                while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                    lines.pop();
+                }
+                read_buf_lenient(lines, &mut line_count, &mut length_errors)
+            }
+        };
 
         if line_count.lt(&3u32) {
-            println!("Sorry: The total number of line entries should be at least 3 line in order to form a valid .aba file, instead it has `{0}` lines. Program aborted", line_count);
-            exit(1);
+            return Err(CemtexerError::Format(format!("The total number of line entries should be at least 3 line in order to form a valid .aba file, instead it has `{0}` lines", line_count)));
         }
 
-        if err_count.gt(&0u32) {
-            println!("{:?}", err);
-            println!("Character count error. Program aborted");
-            exit(1);
+        if !err.is_empty() {
+            return Err(CemtexerError::Format(format!(
+                "Line format error:\n{}",
+                err.join("\n")
+            )));
         }
 
-        println!("This file format is valid, starting content validation.....\n");
-
-        Self {
+        Ok(Self {
             descriptive: entries[0usize].clone(),
             detail: entries[1usize..=(line_count - 2u32) as usize].to_vec(),
             total: entries[(line_count - 1u32) as usize].clone(),
             line_count,
-        }
+            length_errors,
+        })
     }
 
+    /// Validates the content in memory and collects every diagnostic into a report
     pub async fn validate_inner(
         &self,
-        path: impl AsRef<Path> + Display,
-    ) -> Result<(), LineParseError> {
+        ctx: &ValidationContext,
+    ) -> Result<ValidationReport, CemtexerError> {
         let mut detail_line_count = 1u32;
-        let mut error_count = 0u32;
-
-        let mut buf = match File::create(&path).await {
-            Ok(buf) => buf,
-            Err(_) => {
-                println!("Unable to create file at this location. Program aborted");
-                exit(1);
-            }
-        };
+        let mut report = ValidationReport::new();
+        let mut credit = Some(Cents(0u64));
+        let mut debit = Some(Cents(0u64));
+        let mut trace_bsbs: BTreeMap<String, u32> = BTreeMap::new();
+        report.extend(self.length_errors.iter().cloned());
 
-        let (_, descriptive) = DescriptiveBlock::deserialise(&self.descriptive).unwrap();
-        let desc_res = DescriptiveBlock::validate(&descriptive).await?;
-        if !desc_res.is_empty() {
-            error_count += 1;
-            buf.write_all(desc_res.as_bytes()).await.unwrap();
-        }
+        let (_, descriptive) = DescriptiveBlock::deserialise(&self.descriptive)
+            .map_err(|e| CemtexerError::Format(e.to_string()))?;
+        let desc_res = DescriptiveBlock::validate(&descriptive, ctx).await?;
+        report.extend(desc_res);
+        report.extend(
+            DescriptiveBlock::validate_processing_day(
+                &descriptive,
+                &ctx.calendar,
+                ctx.today,
+                &ctx.window,
+            )
+            .await,
+        );
 
         for line in self.detail.iter() {
             detail_line_count += 1u32;
-            let (_, detail_total) = DetailBlock::deserialise(line).unwrap();
-            let detail_res = DetailBlock::validate(&detail_total, &detail_line_count).await?;
-            if !detail_res.is_empty() {
-                error_count += 1;
-                buf.write_all(detail_res.as_bytes()).await.unwrap();
-            }
+            let (_, detail_total) =
+                DetailBlock::deserialise(line).map_err(|e| CemtexerError::Format(e.to_string()))?;
+            let detail_res = DetailBlock::validate(&detail_total, &detail_line_count, ctx).await?;
+            report.extend(detail_res);
+
+            // Lines with malformed amounts or codes are reported above and left out of the sums
+            let (line_credit, line_debit) = detail_total.credit_debit();
+            credit = credit.and_then(|sum| sum.checked_add(line_credit));
+            debit = debit.and_then(|sum| sum.checked_add(line_debit));
+            trace_bsbs
+                .entry(detail_total.trace_bsb.clone())
+                .or_insert(detail_line_count);
         }
+        report.extend(DescriptiveBlock::validate_trace_bsbs(&descriptive, &trace_bsbs, ctx).await);
 
-        let (_, block_total) = TotalBlock::deserialise(&self.total).unwrap();
+        let (_, block_total) = TotalBlock::deserialise(&self.total)
+            .map_err(|e| CemtexerError::Format(e.to_string()))?;
         let total_res = TotalBlock::validate(&block_total, &(self.line_count - 2u32)).await?;
-        if !total_res.is_empty() {
-            error_count += 1;
-            buf.write_all(total_res.as_bytes()).await.unwrap();
+        report.extend(total_res);
+        // Sums that overflow the 10 digit fields cannot be compared with the total record
+        let sums_res = match (credit, debit) {
+            (Some(credit), Some(debit)) => {
+                TotalBlock::validate_detail_sums(
+                    &block_total,
+                    &(self.line_count - 2u32),
+                    credit,
+                    debit,
+                )
+                .await
+            }
+            _ => {
+                TotalBlock::validate_sum_overflow(
+                    &block_total,
+                    &(self.line_count - 2u32),
+                    credit,
+                    debit,
+                )
+                .await
+            }
+        };
+        report.extend(sums_res);
+
+        Ok(report)
+    }
+}
+
+impl CemtexInner {
+    fn typed_details(&self) -> Result<Vec<Detail>, CemtexerError> {
+        let mut details: Vec<Detail> = Vec::new();
+
+        for (line, detail) in (2u32..).zip(self.detail.iter()) {
+            let (_, block) = DetailBlock::deserialise(detail)
+                .map_err(|e| CemtexerError::Format(format!("At line {}: {}", line, e)))?;
+            let typed = Detail::try_from(&block)
+                .map_err(|e| CemtexerError::Format(format!("At line {}: {}", line, e)))?;
+            details.push(typed);
         }
 
-        if error_count.eq(&0u32) {
-            println!("File content validation successful!");
-            buf.write_all(b"No errors detected").await.unwrap();
+        Ok(details)
+    }
+}
+
+impl FromStr for CemtexInner {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_with_mode(s, ParseMode::Strict)
+    }
+}
+
+fn read_buf(lines: Vec<String>, line_count: &mut u32, err: &mut Vec<String>) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+
+    for line in lines {
+        *line_count += 1;
+
+        let len = line.chars().count();
+        if !line.is_ascii() {
+            err.push(format!(
+                "at line {} it must only contain ASCII characters, not {}",
+                line_count,
+                non_ascii_chars(&line)
+            ));
+        } else if !len.eq(&120usize) {
+            err.push(format!("at line {} the character count is {}, but in order to form a valid line it must be exactly 120 characters", line_count, len));
         } else {
-            println!(
-                "Some errors detected and a report is generated at location: {}",
-                &path
-            );
+            entries.push(line);
+        }
+    }
+    entries
+}
+
+/// Line termination found in .aba content
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct LineTermination {
+    pub lf: usize,
+    pub crlf: usize,
+    pub final_newline: bool,
+}
+
+impl LineTermination {
+    pub fn detect(content: &str) -> Self {
+        let breaks = content.matches('\n').count();
+        let crlf = content.matches("\r\n").count();
+
+        Self {
+            lf: breaks - crlf,
+            crlf,
+            final_newline: content.ends_with('\n'),
         }
+    }
+
+    /// The line ending used throughout, None when mixed or when there is no line break at all
+    pub fn style(&self) -> Option<LineEnding> {
+        match (self.lf, self.crlf) {
+            (0usize, 0usize) => None,
+            (_, 0usize) => Some(LineEnding::Lf),
+            (0usize, _) => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
+}
 
-        Ok(())
+impl Display for LineTermination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.style() {
+            Some(style) => write!(f, "{} line endings", style.name())?,
+            None if self.lf.eq(&0usize) => write!(f, "no line endings")?,
+            None => write!(
+                f,
+                "mixed line endings, {} LF and {} CRLF",
+                self.lf, self.crlf
+            )?,
+        }
+        match self.final_newline {
+            true => write!(f, " with a final newline"),
+            false => write!(f, " without a final newline"),
+        }
     }
 }
 
-async fn read_buf<'a>(
-    buf: BufReader<File>,
+/// Restores the trailing blanks that email clients and editors strip from the descriptive and
+/// total lines, both end with 40 blanks by spec. Lines are only padded when nothing but blanks
+/// is missing, detail lines end with the tax withholding amount and are left as they are.
+/// Line endings are kept, every repaired line is reported as a warning
+pub fn repair_padding(content: &str) -> (String, Vec<Diagnostic>) {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let last = lines.iter().rposition(|line| !line.trim().is_empty());
+    let mut repaired = String::new();
+    let mut repairs: Vec<Diagnostic> = Vec::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let text = body.trim_start_matches('\u{feff}');
+        let len = text.chars().count();
+
+        let e = match idx {
+            0usize => LineParseError::DescriptivePaddingRepaired(len),
+            n if last.is_some_and(|last| last.eq(&n) && n.gt(&0usize)) => {
+                LineParseError::TotalPaddingRepaired(len)
+            }
+            _ => {
+                repaired.push_str(line);
+                continue;
+            }
+        };
+
+        if len.ge(&80usize) && len.lt(&120usize) && text.chars().skip(80usize).all(|c| c.eq(&' ')) {
+            repairs.push(Diagnostic::warning(&e, idx as u32 + 1u32, body));
+            repaired.push_str(body);
+            repaired.push_str(&" ".repeat(120usize - len));
+            repaired.push_str(ending);
+        } else {
+            repaired.push_str(line);
+        }
+    }
+
+    (repaired, repairs)
+}
+
+/// Pads short lines with blanks and truncates long ones so that every field can still be
+/// validated, each mis-sized line is reported against the block it belongs to
+fn read_buf_lenient(
+    lines: Vec<String>,
     line_count: &mut u32,
-    err_count: &mut u32,
-    err: &mut Vec<String>,
+    err: &mut Vec<Diagnostic>,
 ) -> Vec<String> {
+    let last = lines.len() as u32;
     let mut entries: Vec<String> = Vec::new();
-    let mut lines = buf.lines();
 
-    while let Some(line) = lines.next_line().await.unwrap() {
+    for line in lines {
         *line_count += 1;
+        let len = line.chars().count();
 
-        if !line.len().eq(&120usize) {
-            err.push(format!("at line {} the character count is {}, but in order to form a valid line it must be exactly 120 characters", line_count, line.len()));
-            *err_count += 1;
-        } else {
+        if !line.is_ascii() {
+            let chars = non_ascii_chars(&line);
+            let e = match *line_count {
+                1u32 => LineParseError::DescriptiveNonAscii(chars),
+                n if n.eq(&last) => LineParseError::TotalNonAscii(chars),
+                n => LineParseError::DetailNonAscii(n, chars),
+            };
+            err.push(Diagnostic::new(&e, *line_count, &line));
+        }
+
+        if len.eq(&120usize) {
             entries.push(line);
+            continue;
         }
+
+        let e = match *line_count {
+            1u32 => LineParseError::DescriptiveLineLength(len),
+            n if n.eq(&last) => LineParseError::TotalLineLength(len),
+            n => LineParseError::DetailLineLength(n, len),
+        };
+        err.push(Diagnostic::new(&e, *line_count, &line));
+        entries.push(
+            line.chars()
+                .chain(std::iter::repeat(' '))
+                .take(120usize)
+                .collect(),
+        );
     }
     entries
 }
+
+#[tokio::test]
+async fn test_cemtex_missing_file() {
+    let res = Cemtex::new("/nonexistent/path/to/file.aba").await;
+    assert!(matches!(res, Err(CemtexerError::Io(_))));
+}
+
+#[tokio::test]
+async fn test_cemtex_from_memory() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1123-456157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000012340000000000                        000001                                        \n";
+    let from_str: Cemtex = aba.parse().unwrap();
+    let from_reader = Cemtex::from_reader(aba.as_bytes()).unwrap();
+    assert_eq!(from_str, from_reader);
+    assert_eq!(from_str, Cemtex::from_bytes(aba.as_bytes()).unwrap());
+    assert_eq!(from_str.inner.detail.len(), 1);
+    assert!(Cemtex::from_str("too short").is_err());
+
+    let crlf = format!("\u{feff}{}", aba.replace('\n', "\r\n"));
+    assert_eq!(from_str, Cemtex::from_str(&crlf).unwrap());
+    assert_eq!(from_str, Cemtex::from_reader(crlf.as_bytes()).unwrap());
+}
+
+#[tokio::test]
+async fn test_cemtex_detail_sums() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000200000000020000000000000                        000001                                        \n";
+    let report = Cemtex::from_str(aba)
+        .unwrap()
+        .validate(&ValidationContext::new(time::macros::date!(2024 - 10 - 01)))
+        .await
+        .unwrap();
+    let rules: Vec<&str> = report.iter().map(|d| d.rule).collect();
+    // The settlement date of 23 April 2010 has passed
+    assert_eq!(rules, vec!["ABA113", "ABA314", "ABA315"]);
+
+    let report = Cemtex::from_str(aba)
+        .unwrap()
+        .validate(&ValidationContext::new(time::macros::date!(2010 - 04 - 01)))
+        .await
+        .unwrap();
+    let rules: Vec<&str> = report.iter().map(|d| d.rule).collect();
+    assert_eq!(rules, vec!["ABA314", "ABA315"]);
+}
+
+#[tokio::test]
+async fn test_cemtex_directory() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000012340000000000                        000001                                        \n";
+    let cemtex = Cemtex::from_str(aba).unwrap();
+    let directory: crate::bsb::BsbDirectory =
+        "062-000,CBA,Sydney,,,NSW,2000,PEH\n".parse().unwrap();
+    let ctx = ValidationContext::new(time::macros::date!(2024 - 10 - 01)).with_directory(directory);
+    let report = cemtex.validate(&ctx).await.unwrap();
+    let rules: Vec<&str> = report.iter().map(|d| d.rule).collect();
+    assert_eq!(rules, vec!["ABA113", "ABA202"]);
+}
+
+#[tokio::test]
+async fn test_cemtex_to_csv() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231W130000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000000000000001234                        000001                                        \n";
+    let cemtex = Cemtex::from_str(aba).unwrap();
+    assert_eq!(
+        cemtex.to_csv().unwrap(),
+        "012-002,157108231,S R SMITH,12.34,TEST BATCH,12.00,13,W,062-000,12223123,MY ACCOUNT\n"
+    );
+
+    let settings = cemtex.to_settings().unwrap();
+    assert_eq!(settings.apca_number, "111111");
+    assert_eq!(settings.trace_account_name, "MY ACCOUNT");
+    assert_eq!(
+        DescriptiveBlock::from(settings).to_string(),
+        aba.lines().next().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_cemtex_lenient() {
+    let aba = "\u{feff}0                 01CBA       MY NAME                   1111111004231633  230410\r\n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200  \r\n\
+               7999-999            000000123400000012340000000000                        000001\r\n\r\n";
+    assert!(matches!(
+        Cemtex::from_str(aba),
+        Err(CemtexerError::Format(_))
+    ));
+
+    let cemtex = Cemtex::from_str_with_mode(aba, ParseMode::Lenient).unwrap();
+    let report = cemtex
+        .validate(&ValidationContext::new(time::macros::date!(2024 - 10 - 01)))
+        .await
+        .unwrap();
+    let rules: Vec<(&str, u32)> = report.iter().map(|d| (d.rule, d.line)).collect();
+    assert_eq!(
+        rules,
+        vec![("ABA100", 1), ("ABA200", 2), ("ABA300", 3), ("ABA113", 1)]
+    );
+}
+
+#[tokio::test]
+async fn test_cemtex_non_ascii() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1123-456157108231 530000001234S R SMITHÉ                      TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000012340000000000                        000001                                        \n";
+    match Cemtex::from_str(aba) {
+        Err(CemtexerError::Format(e)) => assert!(e.contains("ASCII characters, not 'É'")),
+        _ => panic!("non-ASCII line must be rejected"),
+    }
+
+    let cemtex = Cemtex::from_str_with_mode(aba, ParseMode::Lenient).unwrap();
+    let report = cemtex
+        .validate(&ValidationContext::new(time::macros::date!(2024 - 10 - 01)))
+        .await
+        .unwrap();
+    assert!(report
+        .iter()
+        .any(|d| d.rule.eq("ABA216") && d.line.eq(&2u32)));
+    assert!(!report.iter().any(|d| d.rule.eq("ABA200")));
+}
+
+#[test]
+fn test_repair_padding() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410\r\n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\r\n\
+               7999-999            000000123400000012340000000000                        000001   \r\n";
+    let (repaired, repairs) = repair_padding(aba);
+    let rules: Vec<(&str, u32)> = repairs.iter().map(|d| (d.rule, d.line)).collect();
+    assert_eq!(rules, vec![("ABA117", 1), ("ABA318", 3)]);
+    assert!(repairs.iter().all(|d| d.severity.eq(&Severity::Warning)));
+    assert!(repaired.lines().all(|line| line.len().eq(&120usize)));
+    assert!(Cemtex::from_str(&repaired).is_ok());
+
+    let (unchanged, repairs) = repair_padding(&repaired);
+    assert_eq!(unchanged, repaired);
+    assert!(repairs.is_empty());
+}
+
+#[test]
+fn test_line_termination() {
+    let crlf = LineTermination::detect("0\r\n1\r\n7\r\n");
+    assert_eq!(crlf.style(), Some(LineEnding::Crlf));
+    assert_eq!(crlf.to_string(), "CRLF line endings with a final newline");
+
+    let mixed = LineTermination::detect("0\r\n1\n7");
+    assert_eq!(mixed.style(), None);
+    assert_eq!(
+        mixed.to_string(),
+        "mixed line endings, 1 LF and 1 CRLF without a final newline"
+    );
+}
+
+#[tokio::test]
+async fn test_cemtex_lenient_every_line() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \r\n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH\r\n\
+               1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\r\n\
+               1012-002\r\n\
+               7999-999            000000246800000024680000000000                        000003                                        \r\n";
+    let cemtex = Cemtex::from_str_with_mode(aba, ParseMode::Lenient).unwrap();
+    assert_eq!(cemtex.inner.detail.len(), 3);
+    let report = cemtex
+        .validate(&ValidationContext::new(time::macros::date!(2024 - 10 - 01)))
+        .await
+        .unwrap();
+    let lengths: Vec<u32> = report
+        .iter()
+        .filter(|d| d.rule.eq("ABA200"))
+        .map(|d| d.line)
+        .collect();
+    assert_eq!(lengths, vec![2, 4]);
+}
+
+#[tokio::test]
+async fn test_cemtex_sum_overflow() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231 539000000000S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00000000\n\
+               1012-002157108231 539000000000S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00000000\n\
+               7999-999            999999999999999999990000000000                        000002                                        \n";
+    let report = Cemtex::from_str(aba)
+        .unwrap()
+        .validate(&ValidationContext::new(time::macros::date!(2010 - 04 - 01)))
+        .await
+        .unwrap();
+    let rules: Vec<(&str, u32)> = report.iter().map(|d| (d.rule, d.line)).collect();
+    assert_eq!(rules, vec![("ABA319", 4)]);
+}
+
+#[tokio::test]
+async fn test_cemtex_fix_unreadable() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231 53  12.34   S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000012340000000000                        000001                                        \n";
+    match Cemtex::from_str(aba).unwrap().fix().await {
+        Err(CemtexerError::Validation(e)) => assert!(e[0].starts_with("At line 2 ")),
+        _ => panic!("an unreadable amount must not be fixed"),
+    }
+}
@@ -1,5 +1,8 @@
 //! Command line options and associated functions
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::report::*;
+use crate::types::*;
 
 /// Command line options
 #[derive(Parser)]
@@ -9,6 +12,32 @@ use clap::{Args, Parser, Subcommand};
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
+    #[clap(
+        long,
+        global = true,
+        help = "Validate BSBs against this APCA BSB extract instead of the embedded list"
+    )]
+    pub bsb_file: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        value_name = "YEAR",
+        help = "First year of the 100 years that two-digit settlement years fall in, 2000 by default"
+    )]
+    pub century_start: Option<i32>,
+    #[clap(
+        long,
+        global = true,
+        help = "Load extra public holidays from an ICS calendar or a CSV file of date, name and jurisdiction"
+    )]
+    pub holidays: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        help = "Observe the public holidays of this state on top of the national ones"
+    )]
+    pub state: Option<AuState>,
 }
 
 /// Subcommands
@@ -35,17 +64,102 @@ pub enum Commands {
         #[clap(flatten)]
         path: AbacheckSub,
     },
+    #[clap(
+        about = "Convert Cemtex .aba file back into .csv compliant file, try run:\n \"cemtexer abadump --aba /path/to/someaba.aba --csv /path/to/output.csv\"\nType: cemtex abadump -h for all options"
+    )]
+    Abadump {
+        #[clap(flatten)]
+        path: AbadumpSub,
+    },
+    #[clap(
+        about = "Repair common defects in Cemtex .aba file and print the changes made, try run:\n \"cemtexer abafix --aba /path/to/someaba.aba --output /path/to/fixed.aba\"\nType: cemtex abafix -h for all options"
+    )]
+    Abafix {
+        #[clap(flatten)]
+        path: AbafixSub,
+    },
+    #[clap(
+        about = "Manage the BSB directory used for validation, try run:\n \"cemtexer bsb import /path/to/BSBDirectory.csv\"\nType: cemtex bsb -h for all options"
+    )]
+    Bsb {
+        #[clap(subcommand)]
+        command: BsbCommands,
+    },
+}
+
+/// Subcommands of the Bsb command
+#[non_exhaustive]
+#[derive(Subcommand, Clone)]
+pub enum BsbCommands {
+    #[clap(
+        about = "Import an APCA BSB extract into the local cache that validation prefers over the embedded list"
+    )]
+    Import {
+        path: String,
+        #[clap(
+            long,
+            help = "The file is a monthly delta, rows marked C or M in the last column are closed or merged"
+        )]
+        delta: bool,
+    },
+    #[clap(about = "Print the branch details of a BSB")]
+    Lookup { bsb: String },
+}
+
+/// Formats payment batches are exchanged in besides .aba
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default, ValueEnum)]
+pub enum DataFormat {
+    #[default]
+    Csv,
+    Json,
 }
 
 /// Suboptions for Abagen command
 #[derive(Args, Clone)]
 pub struct AbagenSub {
-    #[clap(long)]
-    pub template: String,
-    #[clap(long)]
+    #[clap(
+        long,
+        help = "Required for csv input, json input carries its own descriptive record"
+    )]
+    pub template: Option<String>,
+    #[clap(
+        long,
+        alias = "input",
+        help = "Input file in the format given by --input-format"
+    )]
     pub csv: String,
+    #[clap(long, value_enum, default_value_t = DataFormat::Csv)]
+    pub input_format: DataFormat,
     #[clap(long)]
     pub aba: String,
+    #[clap(
+        long,
+        help = "Append a balancing record against the trace account, same as self_balancing = \"Y\" in the template"
+    )]
+    pub self_balancing: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "Overrides line_ending in the template, LF by default"
+    )]
+    pub line_ending: Option<LineEnding>,
+    #[clap(
+        long,
+        help = "Leave the total record without a line break, same as omit_final_newline = \"Y\" in the template"
+    )]
+    pub omit_final_newline: bool,
+    #[clap(
+        long,
+        help = "Transliterate and uppercase csv text fields into the BECS character set, same as normalise_text = \"Y\" in the template"
+    )]
+    pub normalise_text: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "Overrides the over-length policy of every csv text field in the template"
+    )]
+    pub overflow: Option<OverflowPolicy>,
 }
 
 /// Suboptions for Abacheck command
@@ -55,4 +169,57 @@ pub struct AbacheckSub {
     pub aba: String,
     #[clap(long)]
     pub report: String,
+    #[clap(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+    #[clap(long, help = "Print error counts per block to stdout")]
+    pub summary: bool,
+    #[clap(long, help = "Report an error unless the net total is zero")]
+    pub require_balanced: bool,
+    #[clap(
+        long,
+        help = "Validate every field of mis-sized lines and report their length as errors instead of aborting"
+    )]
+    pub lenient: bool,
+    #[clap(
+        long,
+        help = "Restore trailing blanks stripped from the descriptive and total lines before validating"
+    )]
+    pub repair_padding: bool,
+    #[clap(
+        long,
+        requires = "repair_padding",
+        help = "Write the file with restored padding to this path"
+    )]
+    pub repaired: Option<String>,
+}
+
+/// Suboptions for Abadump command
+#[derive(Args, Clone)]
+pub struct AbadumpSub {
+    #[clap(long)]
+    pub aba: String,
+    #[clap(
+        long,
+        alias = "output",
+        help = "Output file in the format given by --format"
+    )]
+    pub csv: String,
+    #[clap(long, value_enum, default_value_t = DataFormat::Csv)]
+    pub format: DataFormat,
+    #[clap(
+        long,
+        help = "Also write the template recovered from the descriptive record and first trace account"
+    )]
+    pub template: Option<String>,
+}
+
+/// Suboptions for Abafix command
+#[derive(Args, Clone)]
+pub struct AbafixSub {
+    #[clap(long)]
+    pub aba: String,
+    #[clap(long)]
+    pub output: String,
+    #[clap(long, help = "Write the diff to this path instead of stdout")]
+    pub diff: Option<String>,
 }
@@ -1,13 +1,21 @@
-use std::{error::Error, fmt::Write as fw, path::Path, process::exit};
-use tokio::{fs::File, io::AsyncWriteExt};
+use std::{path::Path, str::FromStr};
 
 use crate::blocks::*;
+use crate::bsb::*;
+use crate::calendar::*;
 use crate::cemtex::*;
 use crate::cli::*;
+use crate::context::*;
 use crate::csv::*;
+use crate::errors::*;
+use crate::fix::*;
+use crate::helper::*;
+use crate::model::{AbaFile, Cents};
+use crate::report::*;
+use crate::types::*;
 
 /// Subcommand to print out example template
-pub async fn print_example_template() -> std::io::Result<()> {
+pub async fn print_example_template() -> Result<(), CemtexerError> {
     let tpl = include_str!("../data/example");
     print!("{}", tpl);
 
@@ -15,99 +23,428 @@ pub async fn print_example_template() -> std::io::Result<()> {
 }
 
 /// Subcommand to generate template to designated location
-pub async fn generate_template(path: impl AsRef<Path>) -> std::io::Result<()> {
+pub async fn generate_template(path: impl AsRef<Path>) -> Result<(), CemtexerError> {
     let path = path.as_ref().with_extension("toml");
-    let mut buf = match File::create(path).await {
-        Ok(buf) => buf,
-        Err(_) => {
-            println!("Unable to create file at this location. Program aborted");
-            exit(1);
+    tokio::fs::write(path, include_str!("../data/template")).await?;
+
+    Ok(())
+}
+
+/// Builds the context content is validated against as of today in Sydney, a given BSB file
+/// takes precedence over the imported directory, which is not read at all then
+pub async fn validation_context(cli: &Cli) -> Result<ValidationContext, CemtexerError> {
+    let mut ctx = match cli.bsb_file {
+        Some(_) => ValidationContext::new(today()),
+        None => ValidationContext::from_cache(today())?,
+    }
+    .with_window(century_window(cli)?);
+    let mut calendar = HolidayCalendar::new(cli.state);
+    if let Some(path) = &cli.holidays {
+        let holidays = Holiday::from_path(path).await?;
+        println!("Using {} holidays from {}\n", holidays.len(), path);
+        calendar.extend(holidays);
+    }
+    ctx = ctx.with_calendar(calendar);
+
+    match &cli.bsb_file {
+        Some(path) => {
+            let directory = BsbDirectory::from_path(path).await?;
+            println!("Using {} BSBs from {}\n", directory.len(), path);
+            Ok(ctx.with_directory(directory))
         }
+        None => Ok(ctx),
+    }
+}
+
+/// Window two-digit settlement years are read in, 2000 - 2099 unless given
+pub fn century_window(cli: &Cli) -> Result<CenturyWindow, CemtexerError> {
+    match cli.century_start {
+        Some(start) => CenturyWindow::new(start),
+        None => Ok(CenturyWindow::default()),
+    }
+}
+
+/// Subcommand to import an APCA BSB extract into the cache and print the changes
+pub async fn bsb_import(path: String, delta: bool) -> Result<(), CemtexerError> {
+    let kind = match delta {
+        true => BsbImportKind::Delta,
+        false => BsbImportKind::Full,
     };
-    buf.write_all(include_str!("../data/template").as_bytes())
-        .await?;
+    let content = tokio::fs::read_to_string(&path).await?;
+    let (cache, changes) = BsbDirectory::import(&content, kind).await?;
+
+    print!("{}", changes);
+    println!("BSB directory is imported to location {}", cache.display());
+
+    Ok(())
+}
+
+/// Subcommand to print the branch details of a BSB
+pub async fn bsb_lookup(bsb: String, ctx: &ValidationContext) -> Result<(), CemtexerError> {
+    let entry = ctx
+        .directory
+        .get(&bsb)
+        .ok_or_else(|| CemtexerError::Validation(vec![format!("BSB {} is not found", bsb)]))?;
+
+    println!("BSB: {}", entry.bsb);
+    for (name, value) in [
+        ("Institution", &entry.mnemonic),
+        ("Branch", &entry.branch_name),
+        ("Address", &entry.address),
+        ("Suburb", &entry.suburb),
+        ("State", &entry.state),
+        ("Postcode", &entry.postcode),
+        ("Payments", &entry.flags.to_string()),
+    ] {
+        if !value.is_empty() {
+            println!("{}: {}", name, value);
+        }
+    }
 
     Ok(())
 }
 
 /// Subcommand to generate .aba file to designated location
-pub async fn aba_gen(path: AbagenSub) -> std::io::Result<()> {
-    let settle_setting = SettlementSettings::new(path.template).await;
-    SettlementSettings::validate(&settle_setting).await;
+pub async fn aba_gen(path: AbagenSub, ctx: &ValidationContext) -> Result<(), CemtexerError> {
+    let aba = match path.input_format {
+        DataFormat::Csv => csv_to_aba(&path, ctx).await?,
+        DataFormat::Json => json_to_aba(&path, ctx).await?,
+    };
+
+    tokio::fs::write(&path.aba, aba).await?;
+    println!(".aba file succefully generate at location {}", &path.aba);
+
+    Ok(())
+}
+
+async fn csv_to_aba(path: &AbagenSub, ctx: &ValidationContext) -> Result<String, CemtexerError> {
+    let template = path.template.as_ref().ok_or_else(|| {
+        CemtexerError::Template("A template is required to generate from csv input".to_owned())
+    })?;
+    let mut settle_setting = SettlementSettings::new(template, ctx).await?;
+    settle_setting.self_balancing |= path.self_balancing;
+    settle_setting.omit_final_newline |= path.omit_final_newline;
+    settle_setting.normalise_text |= path.normalise_text;
+    if let Some(policy) = path.overflow {
+        settle_setting.overflow = OverflowPolicies::all(policy);
+    }
+    if let Some(line_ending) = path.line_ending {
+        settle_setting.line_ending = line_ending;
+    }
+    if let Some(expression) = settle_setting.settle_date_expression {
+        println!(
+            "Settlement date {} resolved to {} in Sydney time",
+            expression, settle_setting.settle_date
+        );
+    }
+    for warning in SettlementSettings::validate(&settle_setting, ctx).await? {
+        println!("Warning: {}", warning);
+    }
     let desc_block = DescriptiveBlock::from(settle_setting.clone());
 
-    let mut total = 0u32;
+    let mut credit = Cents(0u64);
+    let mut debit = Cents(0u64);
     let mut line_count = 0u32;
 
-    let rec = CsvRecord::read(path.csv).await;
-    let detailvec = read_settings(settle_setting, rec, &mut line_count, &mut total).await;
+    let rec = CsvRecord::read(&path.csv).await?;
+    let mut detailvec = read_settings(
+        settle_setting.clone(),
+        rec,
+        &mut line_count,
+        &mut credit,
+        &mut debit,
+        ctx,
+    )
+    .await?;
+
+    if settle_setting.self_balancing {
+        if let Some(balancing) =
+            RecordWithConf::balancing(settle_setting.clone(), credit, debit).await
+        {
+            let amount = balancing.rec.amount.parse::<Cents>()?;
+            match balancing.rec.is_debit() {
+                true => debit = add_to_total(debit, amount, "debit", &(line_count + 1u32))?,
+                false => credit = add_to_total(credit, amount, "credit", &(line_count + 1u32))?,
+            }
+            line_count += 1u32;
+            detailvec.push(DetailBlock::from(balancing));
+        }
+    }
 
-    let total_record = TotalRecord::new(line_count.to_string(), total.to_string()).await;
+    let total_record = TotalRecord::new(line_count.to_string(), credit, debit).await?;
     let total_block = TotalBlock::from(total_record);
 
-    let mut buf = match File::create(&path.aba).await {
-        Ok(buf) => buf,
-        Err(_) => {
-            println!("Unable to create file at this location. Program aborted");
-            exit(1);
-        }
+    let mut lines: Vec<String> = vec![desc_block.to_string()];
+    lines.extend(detailvec.iter().map(|detail| detail.to_string()));
+    lines.push(total_block.to_string());
+
+    Ok(settle_setting
+        .line_ending
+        .join(&lines, !settle_setting.omit_final_newline))
+}
+
+/// The json is written as is and then validated like any other .aba file
+async fn json_to_aba(path: &AbagenSub, ctx: &ValidationContext) -> Result<String, CemtexerError> {
+    if path.self_balancing {
+        return Err(CemtexerError::Template(
+            "Self-balancing is only supported for csv input".to_owned(),
+        ));
+    }
+    if path.normalise_text || path.overflow.is_some() {
+        return Err(CemtexerError::Template(
+            "Text normalisation and over-length policies are only supported for csv input"
+                .to_owned(),
+        ));
+    }
+
+    let content = tokio::fs::read_to_string(&path.csv).await?;
+    let aba = AbaFile::from_json_in(&content, &ctx.window)?.to_string();
+
+    let report = Cemtex::from_str(&aba)?.validate(ctx).await?;
+    if report.has_errors() {
+        return Err(CemtexerError::Validation(
+            report
+                .filter(|d| d.severity.eq(&Severity::Error))
+                .map(|d| d.summary().to_owned())
+                .collect(),
+        ));
+    }
+
+    let lines: Vec<String> = aba.lines().map(|line| line.to_owned()).collect();
+    Ok(path
+        .line_ending
+        .unwrap_or_default()
+        .join(&lines, !path.omit_final_newline))
+}
+
+/// Subcommand to validation existing .aba file, returns the report so the caller can
+/// decide the exit status
+pub async fn aba_check(
+    path: AbacheckSub,
+    ctx: &ValidationContext,
+) -> Result<ValidationReport, CemtexerError> {
+    println!("Checking file located at {}\n", path.aba);
+    let mode = match path.lenient {
+        true => ParseMode::Lenient,
+        false => ParseMode::Strict,
     };
+    let mut report = ValidationReport::new();
+    let mut content = tokio::fs::read_to_string(&path.aba).await?;
+    println!("Detected {}\n", LineTermination::detect(&content));
 
-    let mut aba = String::new();
-    writeln!(&mut aba, "{}", desc_block).unwrap();
-    for detail in detailvec {
-        writeln!(&mut aba, "{}", detail).unwrap();
+    if path.repair_padding {
+        let (repaired_content, repairs) = repair_padding(&content);
+        for repair in repairs.iter() {
+            println!("{}", repair.summary());
+        }
+        if let Some(repaired) = &path.repaired {
+            tokio::fs::write(repaired, &repaired_content).await?;
+            println!("Repaired file is generated at location: {}", repaired);
+        }
+        report.extend(repairs);
+        content = repaired_content;
     }
-    writeln!(&mut aba, "{}", total_block).unwrap();
 
-    buf.write_all(aba.to_string().as_bytes()).await?;
-    println!(".aba file succefully generate at location {}", &path.aba);
+    let aba = Cemtex::from_str_with_mode(&content, mode)?;
+    println!("This file format is valid, starting content validation.....\n");
+
+    report.extend(Cemtex::validate(&aba, ctx).await?);
+    if path.require_balanced {
+        let balanced = Cemtex::validate_balanced(&aba).await?;
+        if balanced.is_empty() {
+            println!("The file is balanced, the net total is zero\n");
+        }
+        report.extend(balanced);
+    }
+    report.write(&path.report, path.format, &path.aba).await?;
+
+    if report.has_errors() {
+        println!(
+            "Some errors detected and a report is generated at location: {}",
+            &path.report
+        );
+    } else {
+        println!("File content validation successful!");
+    }
+
+    if path.summary {
+        print!("\n{}", report.summary());
+    }
+
+    Ok(report)
+}
+
+/// Subcommand to convert existing .aba file back into .csv or .json file, and optionally its template
+pub async fn aba_dump(path: AbadumpSub, window: &CenturyWindow) -> Result<(), CemtexerError> {
+    let aba = Cemtex::new(&path.aba).await?;
+
+    let content = match path.format {
+        DataFormat::Csv => aba.to_csv()?,
+        DataFormat::Json => aba.to_json_in(window)?,
+    };
+
+    tokio::fs::write(&path.csv, content).await?;
+    println!("File succefully generate at location {}", &path.csv);
+
+    if let Some(template) = path.template {
+        let settings = aba.to_settings_in(window)?;
+        tokio::fs::write(&template, settings.to_template()).await?;
+        println!("Template succefully generate at location {}", &template);
+    }
 
     Ok(())
 }
 
-/// Subcommand to validation existing .aba file
-pub async fn aba_check(path: AbacheckSub) -> std::io::Result<()> {
-    println!("Checking file located at {}\n", path.aba);
-    let aba = Cemtex::new(path.aba).await;
-    let _ = Cemtex::validate(&aba, path.report).await;
+/// Subcommand to repair existing .aba file, the changes are printed as a diff
+pub async fn aba_fix(path: AbafixSub) -> Result<(), CemtexerError> {
+    let content = tokio::fs::read_to_string(&path.aba).await?;
+    let fixed = FixReport::new(&content).await?;
+
+    tokio::fs::write(&path.output, &fixed.content).await?;
+
+    let diff = fixed.diff(&path.aba, &path.output);
+    match &path.diff {
+        Some(diff_path) => {
+            tokio::fs::write(diff_path, diff).await?;
+        }
+        None => print!("{}", diff),
+    }
+
+    println!(
+        "{} line(s) changed, fixed file is generated at location: {}",
+        fixed.changes.len(),
+        &path.output
+    );
 
     Ok(())
 }
 
-async fn read_settings(
+/// Validates every csv record against the settings and converts them into detail blocks
+pub async fn read_settings(
     settle_setting: SettlementSettings,
-    rec: Result<Vec<CsvRecord>, Box<dyn Error>>,
+    rec: Vec<CsvRecord>,
     line_count: &mut u32,
-    total: &mut u32,
-) -> Vec<DetailBlock> {
-    let mut err_count = 0u32;
+    credit: &mut Cents,
+    debit: &mut Cents,
+    ctx: &ValidationContext,
+) -> Result<Vec<DetailBlock>, CemtexerError> {
+    let mut errs: Vec<String> = Vec::new();
+    let mut shortened: Vec<String> = Vec::new();
     let mut rec_conf: Vec<RecordWithConf> = Vec::new();
     let mut detailvec: Vec<DetailBlock> = Vec::new();
 
-    for line in rec.as_ref().unwrap() {
+    for line in rec.iter() {
         *line_count += 1u32;
         let rec_conf_temp = RecordWithConf::new(line, settle_setting.clone()).await;
-        RecordWithConf::validate(&rec_conf_temp, line_count, &mut err_count).await;
+        for substitution in rec_conf_temp.rec.substitutions.iter() {
+            println!("At line {}: {}", line_count, substitution);
+        }
+        if rec_conf_temp
+            .rec
+            .substitutions
+            .iter()
+            .any(|s| s.is_shortened())
+        {
+            shortened.push(line_count.to_string());
+        }
+        match RecordWithConf::validate(&rec_conf_temp, line_count, ctx).await {
+            Ok(warnings) => {
+                for warning in warnings {
+                    println!("Warning: {}", warning);
+                }
+            }
+            Err(CemtexerError::Validation(e)) => errs.extend(e),
+            Err(e) => return Err(e),
+        }
         rec_conf.push(rec_conf_temp);
     }
 
-    if err_count.gt(&0u32) {
-        println!("CSV file validation failed. Program aborted");
-        exit(1);
-    } else {
-        for recs in rec_conf.into_iter() {
-            *total += recs
-                .rec
-                .amount
-                .trim_start_matches('0')
-                .parse::<u32>()
-                .unwrap();
-
-            let detail_block = DetailBlock::from(recs);
-            detailvec.push(detail_block);
+    if !shortened.is_empty() {
+        println!(
+            "Over-length text was shortened in {} row(s): {}",
+            shortened.len(),
+            shortened.join(", ")
+        );
+    }
+
+    if !errs.is_empty() {
+        return Err(CemtexerError::Validation(errs));
+    }
+
+    for (line, recs) in (1u32..).zip(rec_conf) {
+        let amount = recs.rec.amount.parse::<Cents>()?;
+        match recs.rec.is_debit() {
+            true => *debit = add_to_total(*debit, amount, "debit", &line)?,
+            false => *credit = add_to_total(*credit, amount, "credit", &line)?,
         }
+
+        let detail_block = DetailBlock::from(recs);
+        detailvec.push(detail_block);
     }
 
-    detailvec
+    Ok(detailvec)
+}
+
+fn add_to_total(
+    total: Cents,
+    amount: Cents,
+    name: &str,
+    line_count: &u32,
+) -> Result<Cents, CemtexerError> {
+    total.checked_add(amount).ok_or_else(|| {
+        CemtexerError::Validation(vec![format!(
+            "At line {}: the batch {} total exceeds the maximum of ${} that fits the total record",
+            line_count,
+            name,
+            Cents::MAX
+        )])
+    })
+}
+
+#[tokio::test]
+async fn test_aba_check_exit_code() {
+    let dir = std::env::temp_dir().join("cemtexer_test_aba_check_exit_code");
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let check = |name: &str| AbacheckSub {
+        aba: dir.join(format!("{}.aba", name)).display().to_string(),
+        report: dir.join(format!("{}.txt", name)).display().to_string(),
+        format: ReportFormat::Text,
+        summary: false,
+        require_balanced: false,
+        lenient: false,
+        repair_padding: false,
+        repaired: None,
+    };
+    let ctx = ValidationContext::new(time::macros::date!(2024 - 10 - 01));
+    let aba = |settle_date: &str, credit: &str| {
+        format!(
+            "0                 01CBA       MY NAME                   1111111004231633  {}                                        \n\
+             1012-002157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+             7999-999            {}{}0000000000                        000001                                        \n",
+            settle_date, credit, credit
+        )
+    };
+
+    for (name, content, exit_code) in [
+        ("valid", aba("081024", "0000001234"), 0),
+        // Only warns that the settlement date of 23 April 2010 has passed
+        ("warning", aba("230410", "0000001234"), 0),
+        ("invalid", aba("081024", "0000009999"), 1),
+        ("malformed", "0 too short\n".to_owned(), 2),
+    ] {
+        tokio::fs::write(dir.join(format!("{}.aba", name)), content)
+            .await
+            .unwrap();
+        let code = match aba_check(check(name), &ctx).await {
+            Ok(report) => report.exit_code(),
+            Err(e) => e.exit_code(),
+        };
+        assert_eq!(code, exit_code, "{}", name);
+    }
+
+    let Err(e) = aba_check(check("missing"), &ctx).await else {
+        panic!("expected an io error");
+    };
+    assert_eq!(e.exit_code(), 2);
+    let _ = tokio::fs::remove_dir_all(&dir).await;
 }
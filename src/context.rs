@@ -0,0 +1,79 @@
+//! Reference data that .aba and csv content is validated against
+use std::sync::Arc;
+use time::Date;
+
+use crate::bsb::*;
+use crate::calendar::*;
+use crate::errors::*;
+use crate::helper::*;
+
+/// Everything validation depends on besides the content itself, so that the same content
+/// validates the same way for the same context
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationContext {
+    pub directory: Arc<BsbDirectory>,
+    /// Century the two-digit years of settlement dates are read in
+    pub window: CenturyWindow,
+    /// Business days settlement dates are checked against
+    pub calendar: Arc<HolidayCalendar>,
+    /// Reference date for settlement dates in the past and relative expressions,
+    /// usually today in Sydney
+    pub today: Date,
+}
+
+impl ValidationContext {
+    /// The embedded BSB list, the years 2000 - 2099 and national holidays only
+    pub fn new(today: Date) -> Self {
+        Self {
+            directory: BsbDirectory::embedded(),
+            window: CenturyWindow::default(),
+            calendar: Arc::new(HolidayCalendar::default()),
+            today,
+        }
+    }
+
+    /// Same context validating against another BSB directory
+    pub fn with_directory(&self, directory: BsbDirectory) -> Self {
+        Self {
+            directory: Arc::new(directory),
+            ..self.clone()
+        }
+    }
+
+    /// Same context reading two-digit years in another century
+    pub fn with_window(&self, window: CenturyWindow) -> Self {
+        Self {
+            window,
+            ..self.clone()
+        }
+    }
+
+    /// Same context observing the holidays of another calendar
+    pub fn with_calendar(&self, calendar: HolidayCalendar) -> Self {
+        Self {
+            calendar: Arc::new(calendar),
+            ..self.clone()
+        }
+    }
+
+    /// The imported directory when there is one, otherwise the embedded list.
+    /// A cache that cannot be read is reported rather than skipped
+    pub fn from_cache(today: Date) -> Result<Self, CemtexerError> {
+        let context = Self::new(today);
+        match BsbDirectory::from_cache() {
+            Ok(Some(directory)) => Ok(context.with_directory(directory)),
+            Ok(None) => Ok(context),
+            Err(e) => {
+                let reason = match e {
+                    CemtexerError::Format(reason) => reason,
+                    e => e.to_string(),
+                };
+                Err(CemtexerError::Format(format!(
+                    "Unable to read the imported BSB directory at {}, import it again: {}",
+                    BsbDirectory::cache_path().unwrap_or_default().display(),
+                    reason
+                )))
+            }
+        }
+    }
+}
@@ -1,18 +1,23 @@
 //! Various structs to represent csv compliant format
 use config::{Config, File as ConfFile};
-use csv::ReaderBuilder;
-use serde::Deserialize;
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap, convert::AsRef, error::Error, ffi::OsStr, fmt::Display, path::Path,
-    process::exit,
+    cmp::Ordering, collections::HashMap, convert::AsRef, ffi::OsStr, fmt::Display, path::Path,
 };
 
+use crate::calendar::*;
+use crate::context::*;
 use crate::csv::*;
+use crate::errors::*;
+use crate::model::{Cents, Descriptive, Detail, Indicator, SettleDate};
+use crate::parser_utils::*;
 use crate::types::*;
 
 /// Actual csv file struct used for deserialisation.
-/// For the purpose of self integration the format is made rigid
-#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Deserialize)]
+/// For the purpose of self integration the format is made rigid,
+/// the trailing trace columns override the template for that row
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize, Deserialize)]
 pub struct CsvRecord {
     pub bsb: String,
     pub account_number: String,
@@ -22,37 +27,76 @@ pub struct CsvRecord {
     pub comment: Option<String>,
     #[serde(deserialize_with = "optional_tax_withhold")]
     pub tax_withhold: Option<String>,
+    #[serde(default)]
+    pub trans_code: Option<String>,
+    #[serde(default)]
+    pub indicator: Option<String>,
+    #[serde(default)]
+    pub trace_bsb: Option<String>,
+    #[serde(default)]
+    pub trace_account_number: Option<String>,
+    #[serde(default)]
+    pub trace_account_name: Option<String>,
 }
 
 impl CsvRecord {
-    pub async fn read(path: impl AsRef<Path>) -> Result<Vec<CsvRecord>, Box<dyn Error>> {
-        let mut rdr = match ReaderBuilder::new()
+    pub async fn read(path: impl AsRef<Path>) -> Result<Vec<CsvRecord>, CemtexerError> {
+        let file = std::fs::File::open(path)?;
+        let mut rdr = ReaderBuilder::new()
             .has_headers(false)
             .flexible(true)
-            .from_path(path)
-        {
-            Ok(rdr) => rdr,
-            Err(_) => {
-                println!("Unable to open the csv file. Program Aborted");
-                exit(1);
-            }
-        };
+            .from_reader(file);
 
         let mut col: Vec<CsvRecord> = Vec::new();
 
         for res in rdr.deserialize() {
-            let rec: CsvRecord = match res {
-                Ok(rec) => rec,
-                Err(_) => {
-                    print!("Invalid csv file format, most likely a missing comma to denote a field, please refer to self integration guide. Program aborted");
-                    exit(1);
-                }
-            };
+            let rec: CsvRecord = res?;
             col.push(rec);
         }
 
         Ok(col)
     }
+
+    /// Writes records in the same headerless layout that `read` accepts
+    pub fn write_string(records: &[CsvRecord]) -> Result<String, CemtexerError> {
+        let mut wtr = WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_writer(Vec::new());
+
+        for rec in records {
+            wtr.serialize(rec)?;
+        }
+
+        let buf = wtr
+            .into_inner()
+            .map_err(|e| CemtexerError::Io(e.into_error()))?;
+        String::from_utf8(buf).map_err(|e| CemtexerError::Format(e.to_string()))
+    }
+}
+
+impl From<&Detail> for CsvRecord {
+    fn from(detail: &Detail) -> Self {
+        Self {
+            bsb: detail.bsb.to_string(),
+            account_number: detail.account_number.to_string(),
+            client_name: detail.client_name.clone(),
+            amount: detail.amount.to_string(),
+            comment: Some(detail.lodge_ref.clone()),
+            tax_withhold: match detail.tax_withhold.0 {
+                0 => None,
+                _ => Some(detail.tax_withhold.to_string()),
+            },
+            trans_code: Some(detail.trans_code.code().to_owned()),
+            indicator: match detail.indicator {
+                Indicator::Blank => None,
+                indicator => Some(indicator.code().to_owned()),
+            },
+            trace_bsb: Some(detail.trace_bsb.to_string()),
+            trace_account_number: Some(detail.trace_account_number.to_string()),
+            trace_account_name: Some(detail.remitter.clone()),
+        }
+    }
 }
 
 /// Template file struct used in self integration
@@ -63,125 +107,292 @@ pub struct SettlementSettings {
     pub apca_number: String,
     pub file_description: String,
     pub settle_date: String,
+    /// The expression `settle_date` was resolved from, if any
+    pub settle_date_expression: Option<RelativeDate>,
+    /// Whose public holidays apply on top of the national ones
+    pub state: Option<AuState>,
     pub trace_bsb: String,
     pub trace_account_number: String,
     pub trace_account_name: String,
+    /// Append a balancing record against the trace account so the file nets to zero
+    pub self_balancing: bool,
+    pub line_ending: LineEnding,
+    /// Leave the total record without a line break, required by some upload portals
+    pub omit_final_newline: bool,
+    /// Transliterate and uppercase csv text fields into the BECS character set
+    pub normalise_text: bool,
+    pub overflow: OverflowPolicies,
+}
+
+/// Over-length policy of each csv text field
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct OverflowPolicies {
+    pub client_name: OverflowPolicy,
+    pub comment: OverflowPolicy,
+    pub trace_account_name: OverflowPolicy,
+}
+
+impl OverflowPolicies {
+    /// The same policy for every field
+    pub fn all(policy: OverflowPolicy) -> Self {
+        Self {
+            client_name: policy,
+            comment: policy,
+            trace_account_name: policy,
+        }
+    }
 }
 
 impl SettlementSettings {
-    pub async fn new(path: impl AsRef<Path> + AsRef<OsStr>) -> Self {
-        let settings = match Config::builder()
+    pub async fn new(
+        path: impl AsRef<Path> + AsRef<OsStr>,
+        ctx: &ValidationContext,
+    ) -> Result<Self, CemtexerError> {
+        let settings = Config::builder()
             .add_source(ConfFile::from(Path::new(&path)))
             .build()
-        {
-            Ok(settings) => settings,
-            Err(_) => {
-                println!("Unable to open the settings file. Program Aborted");
-                exit(1);
-            }
-        };
+            .map_err(|e| {
+                CemtexerError::Template(format!("Unable to open the settings file: {}", e))
+            })?;
 
         let settings = settings
             .try_deserialize::<HashMap<String, String>>()
-            .unwrap();
-
-        let bank_name = match settings.get("bank_name") {
-            Some(bank_name) => bank_name.trim(),
-            None => {
-                println!("Cannot find value key: user_name...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let user_name = match settings.get("user_name") {
-            Some(user_name) => user_name.trim(),
-            None => {
-                println!("Cannot find value key: user_name...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let apca_number = match settings.get("apca_number") {
-            Some(apca_number) => apca_number.trim(),
-            None => {
-                println!("Cannot find value key: apca_number...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let file_description = match settings.get("file_description") {
-            Some(file_description) => file_description.trim(),
-            None => {
-                println!("Cannot find value key: file_description...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let settle_date = match settings.get("settle_date") {
-            Some(settle_date) => settle_date.trim(),
-            None => {
-                println!("Cannot find value key: settle_date...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let trace_bsb = match settings.get("trace_bsb") {
-            Some(trace_bsb) => trace_bsb.trim(),
-            None => {
-                println!("Cannot find value key: trace_bsb...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
-
-        let trace_account_number = match settings.get("trace_account_number") {
-            Some(trace_account_number) => trace_account_number.trim(),
-            None => {
-                println!("Cannot find value key: trace_account_number...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
+            .map_err(|e| {
+                CemtexerError::Template(format!("Unable to read the settings file: {}", e))
+            })?;
 
-        let trace_account_name = match settings.get("trace_account_name") {
-            Some(trace_account_name) => trace_account_name.trim(),
-            None => {
-                println!("Cannot find value key: trace_account_name...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again");
-                exit(1)
-            }
-        };
+        let state = settings
+            .get("state")
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| value.parse::<AuState>())
+            .transpose()?;
+        let (settle_date, settle_date_expression) =
+            resolve_settle_date(settings_value(&settings, "settle_date")?, state, ctx);
 
-        Self {
-            bank_name: bank_name.to_owned(),
-            user_name: user_name.to_owned(),
-            apca_number: apca_number.to_owned(),
-            file_description: file_description.to_owned(),
-            settle_date: settle_date.to_owned(),
-            trace_bsb: trace_bsb.to_owned(),
-            trace_account_number: trace_account_number.to_owned(),
-            trace_account_name: trace_account_name.to_owned(),
-        }
+        Ok(Self {
+            bank_name: settings_value(&settings, "bank_name")?,
+            user_name: settings_value(&settings, "user_name")?,
+            apca_number: settings_value(&settings, "apca_number")?,
+            file_description: settings_value(&settings, "file_description")?,
+            settle_date,
+            settle_date_expression,
+            state,
+            trace_bsb: settings_value(&settings, "trace_bsb")?,
+            trace_account_number: settings_value(&settings, "trace_account_number")?,
+            trace_account_name: settings_value(&settings, "trace_account_name")?,
+            self_balancing: settings_flag(&settings, "self_balancing")?,
+            line_ending: settings
+                .get("line_ending")
+                .map(|value| value.parse::<LineEnding>())
+                .transpose()?
+                .unwrap_or_default(),
+            omit_final_newline: settings_flag(&settings, "omit_final_newline")?,
+            normalise_text: settings_flag(&settings, "normalise_text")?,
+            overflow: OverflowPolicies {
+                client_name: settings_policy(&settings, "client_name_overflow")?,
+                comment: settings_policy(&settings, "comment_overflow")?,
+                trace_account_name: settings_policy(&settings, "trace_account_name_overflow")?,
+            },
+        })
     }
 
-    pub async fn validate(&self) {
+    /// Returns the warnings that do not stop generation, e.g. a settlement date on a holiday
+    pub async fn validate(&self, ctx: &ValidationContext) -> Result<Vec<String>, CemtexerError> {
         let mut res: Vec<&str> = Vec::new();
 
         let _ = validate_csv_bank_name(&self.bank_name, &mut res);
         let _ = validate_csv_user_name(&self.user_name, &mut res);
+        let _ = validate_csv_becs(
+            &self.user_name,
+            &mut res,
+            ValidationType::DescriptiveSelfName,
+        );
         let _ = validate_csv_apca_number(&self.apca_number, &mut res);
         let _ = validate_csv_file_description(&self.file_description, &mut res);
-        let _ = validate_csv_settle_date(&self.settle_date, &mut res);
-        let _ = validate_bsb(&self.trace_bsb, &mut res, BsbType::DetailTraceBsb);
+        let _ = validate_csv_becs(
+            &self.file_description,
+            &mut res,
+            ValidationType::DescriptiveEntry,
+        );
+        let _ = validate_csv_settle_date_in(&self.settle_date, &mut res, &ctx.window);
+        let _ = validate_bsb_in(
+            &self.trace_bsb,
+            &mut res,
+            BsbType::DetailTraceBsb,
+            &ctx.directory,
+        );
         let _ = validate_account_number(
             &self.trace_account_number,
             &mut res,
             BsbType::DetailTraceBsb,
         );
         let _ = validate_csv_trace_account_name(&self.trace_account_name, &mut res);
+        let _ = validate_csv_becs(
+            &self.trace_account_name,
+            &mut res,
+            ValidationType::DetailRemitter,
+        );
+
+        let mut errs: Vec<String> = res.iter().map(|e| (*e).to_owned()).collect();
+        errs.extend(validate_csv_bank_trace_bsb(
+            &self.bank_name,
+            &self.trace_bsb,
+            &ctx.directory,
+        ));
 
-        if !res.is_empty() {
-            println!("The follow error(s) are detected in the template:");
-            println!("{:?}\n Program Aborted", &res);
-            exit(1)
+        if !errs.is_empty() {
+            return Err(CemtexerError::Validation(
+                errs.iter()
+                    .map(|e| format!("In the template: {}", e))
+                    .collect(),
+            ));
         }
+
+        let calendar = settlement_calendar(ctx, self.state);
+        Ok(
+            validate_csv_processing_day(&self.settle_date, &calendar, ctx.today, &ctx.window)
+                .into_iter()
+                .chain(validate_csv_bank_trace_bsb_range(
+                    &self.bank_name,
+                    &self.trace_bsb,
+                    &ctx.directory,
+                ))
+                .map(|e| format!("In the template: {}", e))
+                .collect(),
+        )
+    }
+}
+
+impl SettlementSettings {
+    /// Recovers the settings from a parsed file, the trace account is taken from the first detail line
+    pub fn from_records(descriptive: &Descriptive, detail: Option<&Detail>) -> Self {
+        Self {
+            bank_name: descriptive.bank_name.clone(),
+            user_name: descriptive.user_name.clone(),
+            apca_number: format!("{:06}", descriptive.apca_number),
+            file_description: descriptive.file_description.clone(),
+            settle_date: descriptive.settle_date.to_string(),
+            settle_date_expression: None,
+            state: None,
+            trace_bsb: detail.map(|d| d.trace_bsb.to_string()).unwrap_or_default(),
+            trace_account_number: detail
+                .map(|d| d.trace_account_number.to_string())
+                .unwrap_or_default(),
+            trace_account_name: detail.map(|d| d.remitter.clone()).unwrap_or_default(),
+            self_balancing: false,
+            line_ending: LineEnding::default(),
+            omit_final_newline: false,
+            normalise_text: false,
+            overflow: OverflowPolicies::default(),
+        }
+    }
+
+    /// Renders the settings as a template file that `new` reads back
+    pub fn to_template(&self) -> String {
+        let mut tpl = String::new();
+        let settle_date = match self.settle_date_expression {
+            Some(expression) => expression.to_string(),
+            None => self.settle_date.clone(),
+        };
+
+        for line in include_str!("../data/template").lines() {
+            let value = match line.split_once('=').map(|(key, _)| key.trim()) {
+                Some("bank_name") => &self.bank_name,
+                Some("user_name") => &self.user_name,
+                Some("apca_number") => &self.apca_number,
+                Some("file_description") => &self.file_description,
+                Some("settle_date") => &settle_date,
+                Some("state") => self.state.map(|state| state.code()).unwrap_or_default(),
+                Some("trace_bsb") => &self.trace_bsb,
+                Some("trace_account_number") => &self.trace_account_number,
+                Some("trace_account_name") => &self.trace_account_name,
+                Some("self_balancing") if self.self_balancing => "Y",
+                Some("self_balancing") => "N",
+                Some("line_ending") => self.line_ending.name(),
+                Some("omit_final_newline") if self.omit_final_newline => "Y",
+                Some("omit_final_newline") => "N",
+                Some("normalise_text") if self.normalise_text => "Y",
+                Some("normalise_text") => "N",
+                Some("client_name_overflow") => policy_name(self.overflow.client_name),
+                Some("comment_overflow") => policy_name(self.overflow.comment),
+                Some("trace_account_name_overflow") => {
+                    policy_name(self.overflow.trace_account_name)
+                }
+                _ => {
+                    tpl.push_str(line);
+                    tpl.push('\n');
+                    continue;
+                }
+            };
+            let (key, _) = line.split_once('=').unwrap_or_default();
+            let value = toml::Value::String(value.to_owned());
+            tpl.push_str(&format!("{}= {}\n", key, value));
+        }
+
+        tpl
+    }
+}
+
+fn settings_value(settings: &HashMap<String, String>, key: &str) -> Result<String, CemtexerError> {
+    match settings.get(key) {
+        Some(value) => Ok(value.trim().to_owned()),
+        None => Err(CemtexerError::Template(format!("Cannot find value key: {}...most likely you have accidentally modified the key name, please fix the keyname or regenerate the template and try again", key))),
+    }
+}
+
+/// Calendar settlement dates are resolved and checked against, the `--state` option taking
+/// precedence over the state of the template
+fn settlement_calendar(ctx: &ValidationContext, state: Option<AuState>) -> HolidayCalendar {
+    ctx.calendar.with_state(ctx.calendar.state().or(state))
+}
+
+/// Expressions such as `+2bd` are resolved against the date of the context and the holidays
+/// of the state, see `settlement_calendar`. Anything else is kept for validation
+fn resolve_settle_date(
+    value: String,
+    state: Option<AuState>,
+    ctx: &ValidationContext,
+) -> (String, Option<RelativeDate>) {
+    match value.parse::<RelativeDate>() {
+        Ok(expression) => {
+            let calendar = settlement_calendar(ctx, state);
+            let date = expression.resolve(ctx.today, &calendar);
+            (SettleDate(date).to_string(), Some(expression))
+        }
+        Err(_) => (value, None),
+    }
+}
+
+/// Optional over-length policy keys, absent keys reject over-length fields as before
+fn settings_policy(
+    settings: &HashMap<String, String>,
+    key: &str,
+) -> Result<OverflowPolicy, CemtexerError> {
+    Ok(settings
+        .get(key)
+        .map(|value| value.parse::<OverflowPolicy>())
+        .transpose()?
+        .unwrap_or_default())
+}
+
+fn policy_name(policy: OverflowPolicy) -> &'static str {
+    match policy {
+        OverflowPolicy::Reject => "REJECT",
+        OverflowPolicy::Truncate => "TRUNCATE",
+        OverflowPolicy::Abbreviate => "ABBREVIATE",
+    }
+}
+
+/// Optional Y/N keys, absent keys default to N so that older templates keep working
+fn settings_flag(settings: &HashMap<String, String>, key: &str) -> Result<bool, CemtexerError> {
+    match settings.get(key).map(|value| value.trim().to_uppercase()) {
+        None => Ok(false),
+        Some(value) if value.eq("Y") => Ok(true),
+        Some(value) if value.eq("N") || value.is_empty() => Ok(false),
+        Some(_) => Err(CemtexerError::Template(format!(
+            "Value key: {} must be either Y or N",
+            key
+        ))),
     }
 }
 
@@ -202,7 +413,9 @@ impl Display for SettlementSettings {
     }
 }
 
-/// Flattened struct for csv data collected
+/// Flattened struct for csv data collected.
+/// An amount with a leading minus sign denotes a debit, e.g. -$12.34,
+/// unless a transaction code is given explicitly in the csv row
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct RecordFlatten {
     pub bsb: String,
@@ -211,24 +424,152 @@ pub struct RecordFlatten {
     pub amount: String,
     pub comment: String,
     pub tax_withhold: String,
+    pub trans_code: String,
+    pub indicator: String,
+    pub trace_bsb: String,
+    pub trace_account_number: String,
+    pub trace_account_name: String,
+    /// Text fields changed by normalisation
+    pub substitutions: Vec<Substitution>,
 }
 
 impl RecordFlatten {
-    pub async fn new(rec: &CsvRecord) -> Self {
-        Self {
+    pub async fn new(rec: &CsvRecord, text_mode: TextMode, overflow: &OverflowPolicies) -> Self {
+        let amount = rec.amount.trim();
+        let explicit_code = rec.trans_code.as_deref().map(str::trim).unwrap_or_default();
+        let (trans_code, amount) = match (explicit_code, amount.strip_prefix('-')) {
+            ("", Some(amount)) => (DEBIT_TRANS_CODE, amount.trim_start()),
+            ("", None) => (CREDIT_TRANS_CODE, amount),
+            (DEBIT_TRANS_CODE, Some(amount)) => (DEBIT_TRANS_CODE, amount.trim_start()),
+            (code, _) => (code, amount),
+        };
+        let indicator = match rec.indicator.as_deref().map(str::trim).unwrap_or_default() {
+            "" => " ",
+            indicator => indicator,
+        };
+
+        let mut flat = Self {
             bsb: rec.bsb.trim().to_owned(),
             account_number: rec.account_number.trim().to_owned(),
             client_name: rec.client_name.trim().to_owned(),
-            amount: normalise_amount(rec.amount.trim().trim_start_matches('$')),
-            comment: rec.comment.as_ref().unwrap().trim().to_owned(),
+            amount: normalise_amount(amount.trim_start_matches('$')),
+            comment: rec.comment.as_deref().unwrap_or_default().trim().to_owned(),
             tax_withhold: normalise_amount(
                 rec.tax_withhold
-                    .as_ref()
-                    .unwrap()
+                    .as_deref()
+                    .unwrap_or_default()
                     .trim()
                     .trim_start_matches('$'),
             ),
+            trans_code: trans_code.to_owned(),
+            indicator: indicator.to_owned(),
+            trace_bsb: rec
+                .trace_bsb
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            trace_account_number: rec
+                .trace_account_number
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            trace_account_name: rec
+                .trace_account_name
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            substitutions: Vec::new(),
+        };
+
+        for (field, value, size, policy) in [
+            (
+                "client_name",
+                &mut flat.client_name,
+                32usize,
+                overflow.client_name,
+            ),
+            ("comment", &mut flat.comment, 18usize, overflow.comment),
+            (
+                "trace_account_name",
+                &mut flat.trace_account_name,
+                16usize,
+                overflow.trace_account_name,
+            ),
+        ] {
+            if text_mode.eq(&TextMode::Normalise) {
+                let normalised = normalise_becs(value);
+                if !normalised.eq(value) {
+                    flat.substitutions.push(Substitution {
+                        field,
+                        kind: SubstitutionKind::Normalised,
+                        before: std::mem::replace(value, normalised.clone()),
+                        after: normalised,
+                    });
+                }
+            }
+
+            if let Some((shortened, applied)) = fit_text(value, size, policy) {
+                let kind = match applied {
+                    OverflowPolicy::Abbreviate => SubstitutionKind::Abbreviated,
+                    _ => SubstitutionKind::Truncated,
+                };
+                flat.substitutions.push(Substitution {
+                    field,
+                    kind,
+                    before: std::mem::replace(value, shortened.clone()),
+                    after: shortened,
+                });
+            }
         }
+
+        flat
+    }
+
+    pub fn is_debit(&self) -> bool {
+        self.trans_code.eq(DEBIT_TRANS_CODE)
+    }
+}
+
+/// How a csv text field was rewritten
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum SubstitutionKind {
+    /// Transliterated into the BECS character set
+    Normalised,
+    /// Shortened to fit its .aba field
+    Abbreviated,
+    Truncated,
+}
+
+/// A csv text field rewritten by `RecordFlatten::new`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Substitution {
+    pub field: &'static str,
+    pub kind: SubstitutionKind,
+    pub before: String,
+    pub after: String,
+}
+
+impl Substitution {
+    pub fn is_shortened(&self) -> bool {
+        !self.kind.eq(&SubstitutionKind::Normalised)
+    }
+}
+
+impl Display for Substitution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            SubstitutionKind::Normalised => "normalised",
+            SubstitutionKind::Abbreviated => "abbreviated",
+            SubstitutionKind::Truncated => "truncated",
+        };
+        write!(
+            f,
+            "{} `{}` {} to `{}`",
+            self.field, self.before, kind, self.after
+        )
     }
 }
 
@@ -241,45 +582,437 @@ pub struct RecordWithConf {
 }
 
 impl RecordWithConf {
+    /// Trace columns left empty in the csv row fall back to the template
     pub async fn new(csv_rec: &CsvRecord, conf: SettlementSettings) -> Self {
-        Self {
-            rec: RecordFlatten::new(csv_rec).await,
-            conf,
+        let text_mode = match conf.normalise_text {
+            true => TextMode::Normalise,
+            false => TextMode::Verbatim,
+        };
+        let mut rec = RecordFlatten::new(csv_rec, text_mode, &conf.overflow).await;
+        for (field, default) in [
+            (&mut rec.trace_bsb, &conf.trace_bsb),
+            (&mut rec.trace_account_number, &conf.trace_account_number),
+            (&mut rec.trace_account_name, &conf.trace_account_name),
+        ] {
+            if field.is_empty() {
+                *field = default.clone();
+            }
         }
+
+        Self { rec, conf }
     }
 
-    pub async fn validate(&self, line_count: &mut u32, err_count: &mut u32) {
+    /// Balancing record that offsets the batch against the trace account so the file nets to zero,
+    /// a debit when credits exceed debits and a credit otherwise. None if the batch is balanced already
+    pub async fn balancing(conf: SettlementSettings, credit: Cents, debit: Cents) -> Option<Self> {
+        let trans_code = match credit.cmp(&debit) {
+            Ordering::Equal => return None,
+            Ordering::Greater => DEBIT_TRANS_CODE,
+            Ordering::Less => BALANCING_CREDIT_TRANS_CODE,
+        };
+
+        let rec = RecordFlatten {
+            bsb: conf.trace_bsb.clone(),
+            account_number: conf.trace_account_number.clone(),
+            client_name: conf.trace_account_name.clone(),
+            amount: credit.abs_diff(debit).0.to_string(),
+            comment: conf.file_description.clone(),
+            tax_withhold: "0".to_owned(),
+            trans_code: trans_code.to_owned(),
+            indicator: " ".to_owned(),
+            trace_bsb: conf.trace_bsb.clone(),
+            trace_account_number: conf.trace_account_number.clone(),
+            trace_account_name: conf.trace_account_name.clone(),
+            substitutions: Vec::new(),
+        };
+
+        Some(Self { rec, conf })
+    }
+
+    pub async fn validate(
+        &self,
+        line_count: &u32,
+        ctx: &ValidationContext,
+    ) -> Result<Vec<String>, CemtexerError> {
         let mut res: Vec<&str> = Vec::new();
 
-        let _ = validate_bsb(&self.rec.bsb, &mut res, BsbType::DetailBsb);
+        let _ = validate_bsb_in(&self.rec.bsb, &mut res, BsbType::DetailBsb, &ctx.directory);
         let _ = validate_account_number(&self.rec.account_number, &mut res, BsbType::DetailBsb);
         let _ = validate_csv_client_name(&self.rec.client_name, &mut res);
+        let _ = validate_csv_becs(
+            &self.rec.client_name,
+            &mut res,
+            ValidationType::DetailClientName,
+        );
         let _ = validate_csv_amount(&self.rec.amount, &mut res);
         let _ = validate_csv_comment(&self.rec.comment, &mut res);
+        let _ = validate_csv_becs(&self.rec.comment, &mut res, ValidationType::DetailLodgeRef);
         let _ = validate_csv_tax_withhold(&self.rec.tax_withhold, &mut res);
+        let _ = validate_csv_trans_code(&self.rec.trans_code, &mut res);
+        let _ = validate_csv_indicator(&self.rec.indicator, &mut res);
+        let _ = validate_bsb_in(
+            &self.rec.trace_bsb,
+            &mut res,
+            BsbType::DetailTraceBsb,
+            &ctx.directory,
+        );
+        let _ = validate_account_number(
+            &self.rec.trace_account_number,
+            &mut res,
+            BsbType::DetailTraceBsb,
+        );
+        let _ = validate_csv_trace_account_name(&self.rec.trace_account_name, &mut res);
+        let _ = validate_csv_becs(
+            &self.rec.trace_account_name,
+            &mut res,
+            ValidationType::DetailRemitter,
+        );
+
+        // The template check already covers rows using the configured trace BSB
+        let mut errs: Vec<String> = res.iter().map(|e| (*e).to_owned()).collect();
+        let mut warnings: Vec<String> = Vec::new();
+        if !self.rec.trace_bsb.eq(&self.conf.trace_bsb) {
+            errs.extend(validate_csv_bank_trace_bsb(
+                &self.conf.bank_name,
+                &self.rec.trace_bsb,
+                &ctx.directory,
+            ));
+            warnings.extend(validate_csv_bank_trace_bsb_range(
+                &self.conf.bank_name,
+                &self.rec.trace_bsb,
+                &ctx.directory,
+            ));
+        }
 
-        if !res.is_empty() {
-            *err_count += 1u32;
-            println!(
-                "The follow error(s) are detected at line {}: {:?}",
-                line_count, &res
-            );
+        if !errs.is_empty() {
+            return Err(CemtexerError::Validation(
+                errs.iter()
+                    .map(|e| format!("At line {}: {}", line_count, e))
+                    .collect(),
+            ));
         }
+
+        Ok(warnings
+            .iter()
+            .map(|e| format!("At line {}: {}", line_count, e))
+            .collect())
     }
 }
 
-/// Helper struct for calculation detail block line count and total amount
+/// Helper struct for calculation detail block line count and total amounts.
+/// The net total is the difference between the credit and debit totals
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct TotalRecord {
     pub line_count: String,
     pub total: String,
+    pub credit: String,
+    pub debit: String,
 }
 
 impl TotalRecord {
-    pub async fn new(line_count: String, total: String) -> Self {
-        Self {
-            line_count: right_adjust(&line_count, 6, FillStrategy::Zero),
-            total: right_adjust(&total, 10, FillStrategy::Zero),
+    pub async fn new(
+        line_count: String,
+        credit: Cents,
+        debit: Cents,
+    ) -> Result<Self, CemtexerError> {
+        for (name, amount) in [("credit", credit), ("debit", debit)] {
+            if amount.gt(&Cents::MAX) {
+                return Err(CemtexerError::Validation(vec![format!(
+                    "The batch {} total of ${} exceeds the maximum of ${} that fits the total record",
+                    name,
+                    amount,
+                    Cents::MAX
+                )]));
+            }
         }
+
+        Ok(Self {
+            line_count: right_adjust(&line_count, 6, FillStrategy::Zero),
+            total: credit.abs_diff(debit).to_field(10),
+            credit: credit.to_field(10),
+            debit: debit.to_field(10),
+        })
+    }
+}
+
+#[test]
+fn test_settings_value() {
+    let mut settings: HashMap<String, String> = HashMap::new();
+    settings.insert("bank_name".to_owned(), " ANZ ".to_owned());
+    assert_eq!(settings_value(&settings, "bank_name").unwrap(), "ANZ");
+    assert!(matches!(
+        settings_value(&settings, "user_name"),
+        Err(CemtexerError::Template(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_debit_record() {
+    let rec = CsvRecord {
+        amount: "-$12.34".to_owned(),
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Verbatim, &OverflowPolicies::default()).await;
+    assert!(flat.is_debit());
+    assert_eq!(flat.amount, "1234");
+
+    let total = TotalRecord::new("2".to_owned(), Cents(1000), Cents(1234))
+        .await
+        .unwrap();
+    assert_eq!(total.total, "0000000234");
+    assert_eq!(total.debit, "0000001234");
+    assert!(TotalRecord::new("2".to_owned(), Cents(u64::MAX), Cents(0))
+        .await
+        .is_err());
+}
+
+#[test]
+fn test_read_optional_columns() {
+    let data =
+        "063-000,1234567,Alice Smith,37.00,,\n063-001,9876543,Bob Smith,58.00,Purchase,,56,N\n";
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let recs: Vec<CsvRecord> = rdr.deserialize().collect::<Result<_, _>>().unwrap();
+    assert_eq!(recs[0].trans_code, None);
+    assert_eq!(recs[1].trans_code.as_deref(), Some("56"));
+    assert_eq!(recs[1].indicator.as_deref(), Some("N"));
+}
+
+#[tokio::test]
+async fn test_explicit_trans_code() {
+    let rec = CsvRecord {
+        amount: "-12.34".to_owned(),
+        trans_code: Some("53".to_owned()),
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Verbatim, &OverflowPolicies::default()).await;
+    assert_eq!(flat.indicator, " ");
+    let mut res: Vec<&str> = Vec::new();
+    assert!(!validate_csv_amount(&flat.amount, &mut res));
+
+    let rec = CsvRecord {
+        amount: "12.34".to_owned(),
+        trans_code: Some("13".to_owned()),
+        indicator: Some("W".to_owned()),
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Verbatim, &OverflowPolicies::default()).await;
+    assert!(flat.is_debit());
+    assert_eq!(flat.indicator, "W");
+}
+
+#[tokio::test]
+async fn test_bank_trace_bsb() {
+    let conf = SettlementSettings {
+        bank_name: "NAB".to_owned(),
+        user_name: "ACME".to_owned(),
+        apca_number: "123456".to_owned(),
+        file_description: "PAYROLL".to_owned(),
+        settle_date: "010124".to_owned(),
+        trace_bsb: "062-000".to_owned(),
+        trace_account_number: "12345678".to_owned(),
+        trace_account_name: "ACME".to_owned(),
+        ..Default::default()
+    };
+    // The embedded list has no mnemonics, the institution is only guessed from the BSB range
+    let ctx = ValidationContext::new(time::macros::date!(2024 - 10 - 01));
+    let warnings = conf.validate(&ctx).await.unwrap();
+    assert_eq!(
+        warnings.last().map(|w| w.as_str()),
+        Some("In the template: Bank name NAB may not own the trace BSB 062-000, which is in the BSB range of CBA, import the BSB directory to confirm its institution")
+    );
+
+    let directory: crate::bsb::BsbDirectory =
+        "062-000,CBA,Sydney,,,NSW,2000,PEH\n".parse().unwrap();
+    let listed = ctx.with_directory(directory);
+    let Err(CemtexerError::Validation(errs)) = conf.validate(&listed).await else {
+        panic!("expected a validation error");
+    };
+    assert_eq!(
+        errs,
+        vec!["In the template: Bank name NAB does not own the trace BSB 062-000, which belongs to CBA, use CBA instead"]
+    );
+
+    let conf = SettlementSettings {
+        bank_name: "CBA".to_owned(),
+        ..conf
+    };
+    assert!(conf.validate(&listed).await.is_ok());
+    assert!(validate_csv_bank_trace_bsb("CBA", "083-004", &ctx.directory).is_none());
+    assert!(validate_csv_bank_trace_bsb_range("CBA", "083-004", &ctx.directory).is_some());
+    let mut res: Vec<&str> = Vec::new();
+    assert!(!validate_csv_bank_name("NA", &mut res));
+}
+
+#[tokio::test]
+async fn test_balancing_record() {
+    let conf = SettlementSettings {
+        trace_bsb: "062-000".to_owned(),
+        trace_account_number: "12345678".to_owned(),
+        ..Default::default()
+    };
+    let bal = RecordWithConf::balancing(conf.clone(), Cents(5000), Cents(1000))
+        .await
+        .unwrap();
+    assert!(bal.rec.is_debit());
+    assert_eq!(bal.rec.amount, "4000");
+    assert_eq!(bal.rec.bsb, "062-000");
+    assert!(RecordWithConf::balancing(conf, Cents(10), Cents(10))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_trace_override() {
+    let conf = SettlementSettings {
+        trace_bsb: "062-000".to_owned(),
+        trace_account_number: "12345678".to_owned(),
+        trace_account_name: "MY ACCOUNT".to_owned(),
+        ..Default::default()
+    };
+    let rec = CsvRecord {
+        trace_bsb: Some("012-002".to_owned()),
+        ..Default::default()
+    };
+    let rec_conf = RecordWithConf::new(&rec, conf).await;
+    assert_eq!(rec_conf.rec.trace_bsb, "012-002");
+    assert_eq!(rec_conf.rec.trace_account_name, "MY ACCOUNT");
+}
+
+#[test]
+fn test_resolve_settle_date() {
+    let ctx = ValidationContext::new(time::macros::date!(2024 - 10 - 03));
+    assert_eq!(
+        resolve_settle_date("300422".to_owned(), None, &ctx),
+        ("300422".to_owned(), None)
+    );
+    let (settle_date, expression) =
+        resolve_settle_date("+2BD".to_owned(), Some(AuState::Nsw), &ctx);
+    assert_eq!(expression, Some(RelativeDate::BusinessDays(2u32)));
+    assert!(validate_date_format(&settle_date));
+    // Skips the weekend and the NSW Labour Day on 7 October
+    assert_eq!(settle_date, "081024");
+
+    let conf = SettlementSettings {
+        settle_date,
+        settle_date_expression: expression,
+        ..Default::default()
+    };
+    assert!(conf.to_template().contains("settle_date = \"+2bd\""));
+
+    let vic = ctx.with_calendar(HolidayCalendar::new(Some(AuState::Vic)));
+    assert_eq!(
+        settlement_calendar(&vic, Some(AuState::Nsw)).state(),
+        Some(AuState::Vic)
+    );
+    assert_eq!(
+        settlement_calendar(&ctx, Some(AuState::Nsw)).state(),
+        Some(AuState::Nsw)
+    );
+    // NSW Labour Day is a business day in Victoria
+    let (settle_date, _) = resolve_settle_date("+2BD".to_owned(), Some(AuState::Nsw), &vic);
+    assert_eq!(settle_date, "071024");
+}
+
+#[tokio::test]
+async fn test_template_round_trip() {
+    let conf = SettlementSettings {
+        bank_name: "CBA".to_owned(),
+        user_name: "O\"Brien \\ Cafe\u{301}\tCo".to_owned(),
+        apca_number: "123456".to_owned(),
+        file_description: "PAYROLL".to_owned(),
+        settle_date: "081024".to_owned(),
+        trace_bsb: "062-000".to_owned(),
+        trace_account_number: "12345678".to_owned(),
+        trace_account_name: "Zoë's Account".to_owned(),
+        ..Default::default()
+    };
+    let path = std::env::temp_dir().join("cemtexer_test_template_round_trip.toml");
+    tokio::fs::write(&path, conf.to_template()).await.unwrap();
+    let ctx = ValidationContext::new(time::macros::date!(2024 - 10 - 03));
+    let read = SettlementSettings::new(&path, &ctx).await.unwrap();
+    let _ = tokio::fs::remove_file(&path).await;
+    assert_eq!(read, conf);
+}
+
+#[tokio::test]
+async fn test_normalise_text() {
+    let rec = CsvRecord {
+        client_name: "Zoë O’Brien".to_owned(),
+        comment: Some("INV 42".to_owned()),
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Normalise, &OverflowPolicies::default()).await;
+    assert_eq!(flat.client_name, "ZOE O'BRIEN");
+    assert_eq!(flat.substitutions.len(), 1);
+    assert_eq!(
+        flat.substitutions[0].to_string(),
+        "client_name `Zoë O’Brien` normalised to `ZOE O'BRIEN`"
+    );
+
+    let flat = RecordFlatten::new(&rec, TextMode::Verbatim, &OverflowPolicies::default()).await;
+    assert!(flat.substitutions.is_empty());
+    let mut res: Vec<&str> = Vec::new();
+    assert!(!validate_csv_becs(
+        &flat.client_name,
+        &mut res,
+        ValidationType::DetailClientName
+    ));
+}
+
+#[tokio::test]
+async fn test_overflow_policies() {
+    let rec = CsvRecord {
+        client_name: "Acme Widgets Australia Pty Limited".to_owned(),
+        comment: Some("INVOICE 2024-000123 MARCH".to_owned()),
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Verbatim, &OverflowPolicies::default()).await;
+    let mut res: Vec<&str> = Vec::new();
+    assert!(!validate_csv_client_name(&flat.client_name, &mut res));
+    assert!(flat.substitutions.is_empty());
+
+    let overflow = OverflowPolicies {
+        client_name: OverflowPolicy::Abbreviate,
+        comment: OverflowPolicy::Truncate,
+        ..Default::default()
+    };
+    let flat = RecordFlatten::new(&rec, TextMode::Normalise, &overflow).await;
+    assert_eq!(flat.client_name, "ACME WIDGETS AUSTRALIA PL");
+    assert_eq!(flat.comment, "INVOICE 2024-00012");
+    let kinds: Vec<SubstitutionKind> = flat.substitutions.iter().map(|s| s.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            SubstitutionKind::Normalised,
+            SubstitutionKind::Abbreviated,
+            SubstitutionKind::Truncated
+        ]
+    );
+    assert!(flat.substitutions.iter().skip(1).all(|s| s.is_shortened()));
+}
+
+#[tokio::test]
+async fn test_dump_round_trip() {
+    let aba = "0                 01CBA       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231W130000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               1062-000 12223123 530000005000J CITIZEN                                         062-000 12223123MY ACCOUNT      00000000\n\
+               7999-999            000000376600000050000000001234                        000002                                        \n";
+    let cemtex: crate::cemtex::Cemtex = aba.parse().unwrap();
+    let conf = cemtex.to_settings().unwrap();
+    let csv = cemtex.to_csv().unwrap();
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+    let recs: Vec<CsvRecord> = rdr.deserialize().collect::<Result<_, _>>().unwrap();
+
+    let mut lines: Vec<String> = Vec::new();
+    for rec in &recs {
+        let rec_conf = RecordWithConf::new(rec, conf.clone()).await;
+        lines.push(crate::blocks::DetailBlock::from(rec_conf).to_string());
     }
+    assert_eq!(lines, aba.lines().skip(1).take(2).collect::<Vec<&str>>());
 }
@@ -5,12 +5,48 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{Deserialize, Deserializer};
 use std::ops::Sub;
+use time::Date;
 
+use crate::bsb::*;
+use crate::calendar::*;
+use crate::errors::*;
+use crate::helper::*;
+use crate::model::Bsb;
 use crate::parser_utils::*;
 use crate::types::*;
 
 lazy_static! {
     static ref RE_AMOUNT: Regex = Regex::new(r"^[[:digit:]]{1,8}\.[[:digit:]]{2}$").unwrap();
+    /// Abbreviations applied in order until the text fits, longest phrases first
+    static ref ABBREVIATIONS: Vec<(Regex, &'static str)> = [
+        (r"PROPRIETARY\s+LIMITED", "PL"),
+        (r"PTY\.?\s+LIMITED", "PL"),
+        (r"PTY\.?\s+LTD\.?", "PL"),
+        (r"AS\s+TRUSTEE\s+FOR", "ATF"),
+        (r"THE\s+TRUSTEE\s+FOR", "TTF"),
+        (r"PROPRIETARY", "PTY"),
+        (r"LIMITED", "LTD"),
+        (r"INCORPORATED", "INC"),
+        (r"CORPORATION", "CORP"),
+        (r"COMPANY", "CO"),
+        (r"SUPERANNUATION", "SUPER"),
+        (r"INTERNATIONAL", "INTL"),
+        (r"AUSTRALIAN", "AUST"),
+        (r"AUSTRALIA", "AUST"),
+        (r"ASSOCIATION", "ASSN"),
+        (r"DEPARTMENT", "DEPT"),
+        (r"MANAGEMENT", "MGMT"),
+        (r"SERVICES", "SVCS"),
+        (r"AND", "&"),
+    ]
+    .iter()
+    .map(|(pattern, abbreviation)| {
+        (
+            Regex::new(&format!(r"(?i)\b{}(\s|$)", pattern)).unwrap(),
+            *abbreviation,
+        )
+    })
+    .collect();
 }
 
 /// Custom deserialisation function for the comment field, blank filled
@@ -105,6 +141,21 @@ fn blank_fill(i: &str, size: usize) -> String {
     " ".repeat(remaining)
 }
 
+/// Left adjusts and uppercases a text field
+pub fn fix_text(i: &str, size: usize) -> String {
+    left_adjust(i.trim().to_uppercase().as_str(), size, FillStrategy::Blank)
+}
+
+/// Right adjusts a numeric field, values that are not all digits are left as they are
+pub fn fix_numeric(i: &str, size: usize, strat: FillStrategy) -> String {
+    match i.trim() {
+        trimmed if !trimmed.is_empty() && trimmed.chars().all(|c| c.is_ascii_digit()) => {
+            right_adjust(trimmed, size, strat)
+        }
+        _ => i.to_owned(),
+    }
+}
+
 pub fn normalise_amount(i: &str) -> String {
     if i.contains(".") && RE_AMOUNT.is_match(i) {
         i.replace(".", "")
@@ -120,13 +171,64 @@ pub fn validate_csv_bank_name(i: &str, res: &mut Vec<&str>) -> bool {
     }
 
     let file = include_str!("../data/institution");
-    if !file.contains(i) {
+    if !file.lines().any(|l| l.trim().eq(i)) {
         res.push("Bank name field is not valid");
         return false;
     }
     true
 }
 
+/// Suggests the mnemonic owning the trace BSB when the directory lists another institution
+/// than the bank name
+pub fn validate_csv_bank_trace_bsb(
+    bank: &str,
+    bsb: &str,
+    directory: &BsbDirectory,
+) -> Option<String> {
+    match directory.institution(bsb) {
+        Some(Institution::Listed(mnemonic)) if !mnemonic.eq(bank) => Some(format!(
+            "Bank name {} does not own the trace BSB {}, which belongs to {}, use {} instead",
+            bank, bsb, mnemonic, mnemonic
+        )),
+        _ => None,
+    }
+}
+
+/// Warns when the trace BSB is only in the BSB range of another institution than the bank name
+pub fn validate_csv_bank_trace_bsb_range(
+    bank: &str,
+    bsb: &str,
+    directory: &BsbDirectory,
+) -> Option<String> {
+    match directory.institution(bsb) {
+        Some(Institution::Guessed(mnemonic)) if !mnemonic.eq(bank) => Some(format!(
+            "Bank name {} may not own the trace BSB {}, which is in the BSB range of {}, import the BSB directory to confirm its institution",
+            bank, bsb, mnemonic
+        )),
+        _ => None,
+    }
+}
+
+/// Warns about a settlement date in the past or on a day payments are not processed
+pub fn validate_csv_processing_day(
+    i: &str,
+    calendar: &HolidayCalendar,
+    today: Date,
+    window: &CenturyWindow,
+) -> Option<String> {
+    match validate_processing_day(i.to_owned(), calendar, today, window) {
+        Err(LineParseError::DescriptiveSettleDatePast(date, next)) => Some(format!(
+            "Settlement date {} is in the past, use {} instead",
+            date, next
+        )),
+        Err(LineParseError::DescriptiveSettleDateClosed(date, reason, next)) => Some(format!(
+            "Settlement date {} falls on {}, which is not a processing day, use {} instead",
+            date, reason, next
+        )),
+        _ => None,
+    }
+}
+
 pub fn validate_csv_user_name(i: &str, res: &mut Vec<&str>) -> bool {
     if i.is_empty() || i.len().gt(&26usize) {
         res.push("User name field must not be empty and exceed 26 characters");
@@ -152,7 +254,12 @@ pub fn validate_csv_file_description(i: &str, res: &mut Vec<&str>) -> bool {
 }
 
 pub fn validate_csv_settle_date(i: &str, res: &mut Vec<&str>) -> bool {
-    if i.is_empty() || i.len().gt(&6usize) || !validate_date_format(i) {
+    validate_csv_settle_date_in(i, res, &CenturyWindow::default())
+}
+
+/// Same as `validate_csv_settle_date` for the years of the given window
+pub fn validate_csv_settle_date_in(i: &str, res: &mut Vec<&str>, window: &CenturyWindow) -> bool {
+    if i.is_empty() || i.len().gt(&6usize) || !validate_date_format_in(i, window) {
         res.push("Settlement date field must be in the exact format of DDMMYY");
         return false;
     }
@@ -160,6 +267,16 @@ pub fn validate_csv_settle_date(i: &str, res: &mut Vec<&str>) -> bool {
 }
 
 pub fn validate_bsb(i: &str, res: &mut Vec<&str>, bsb_type: BsbType) -> bool {
+    validate_bsb_in(i, res, bsb_type, &BsbDirectory::embedded())
+}
+
+/// Same as `validate_bsb` against the given BSB directory
+pub fn validate_bsb_in(
+    i: &str,
+    res: &mut Vec<&str>,
+    bsb_type: BsbType,
+    directory: &BsbDirectory,
+) -> bool {
     if i.is_empty() || !i.len().eq(&7usize) {
         match bsb_type {
             BsbType::DetailBsb => {
@@ -172,8 +289,10 @@ pub fn validate_bsb(i: &str, res: &mut Vec<&str>, bsb_type: BsbType) -> bool {
         return false;
     }
 
-    let file = include_str!("../data/bsb");
-    if !file.contains(i) {
+    if !i
+        .parse::<Bsb>()
+        .is_ok_and(|bsb| directory.contains(bsb.as_str()))
+    {
         res.push("BSB code is not valid");
         return false;
     }
@@ -212,11 +331,14 @@ pub fn validate_csv_client_name(i: &str, res: &mut Vec<&str>) -> bool {
 }
 
 pub fn validate_csv_amount(i: &str, res: &mut Vec<&str>) -> bool {
-    if i.is_empty()
+    if i.starts_with('-') {
+        res.push("Amount field with a leading minus sign denotes a debit and must not be combined with a credit transaction code");
+        false
+    } else if i.is_empty()
         || i.replace(".", "").len().gt(&10usize)
         || !validate_number(&i.replace(".", ""))
     {
-        res.push("Amount field must be digits and must not be empty and exceed 10 digits");
+        res.push("Amount field must be digits and must not be empty and exceed 10 digits, i.e. $99,999,999.99");
         false
     } else if i.contains(".") {
         match RE_AMOUNT.is_match(i) {
@@ -239,6 +361,147 @@ pub fn validate_csv_comment(i: &str, res: &mut Vec<&str>) -> bool {
     true
 }
 
+/// Text fields must stay within the BECS character set, see `is_becs_char`
+pub fn validate_csv_becs(i: &str, res: &mut Vec<&str>, fill_type: ValidationType) -> bool {
+    if i.chars().all(is_becs_char) {
+        return true;
+    }
+
+    match fill_type {
+        ValidationType::DescriptiveSelfName => {
+            res.push("User name field must only contain uppercase letters, numerics, blanks and BECS symbols")
+        }
+        ValidationType::DescriptiveEntry => res.push(
+            "File description field must only contain uppercase letters, numerics, blanks and BECS symbols",
+        ),
+        ValidationType::DetailClientName => res.push(
+            "Account name field must only contain uppercase letters, numerics, blanks and BECS symbols",
+        ),
+        ValidationType::DetailLodgeRef => {
+            res.push("Comment field must only contain uppercase letters, numerics, blanks and BECS symbols")
+        }
+        ValidationType::DetailRemitter => res.push(
+            "Trace account name field must only contain uppercase letters, numerics, blanks and BECS symbols",
+        ),
+        _ => return true,
+    }
+    false
+}
+
+/// Transliterates accented letters and typographic punctuation, uppercases and drops
+/// whatever is left outside the BECS character set
+pub fn normalise_becs(i: &str) -> String {
+    let mut res = String::new();
+
+    for c in i.chars() {
+        match c {
+            c if is_becs_char(c.to_ascii_uppercase()) => res.push(c.to_ascii_uppercase()),
+            c if c.is_whitespace() => res.push(' '),
+            c => res.push_str(transliterate(c).unwrap_or_default()),
+        }
+    }
+
+    // Dropped characters must not leave runs of blanks behind
+    res.split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    let res = match c.to_lowercase().next().unwrap_or(c) {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "A",
+        'æ' => "AE",
+        'ç' | 'ć' | 'č' => "C",
+        'ď' | 'đ' | 'ð' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "E",
+        'ğ' => "G",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "I",
+        'ł' => "L",
+        'ñ' | 'ń' | 'ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "O",
+        'œ' => "OE",
+        'ř' => "R",
+        'ś' | 'š' | 'ş' => "S",
+        'ß' => "SS",
+        'ť' => "T",
+        'þ' => "TH",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "U",
+        'ý' | 'ÿ' => "Y",
+        'ź' | 'ż' | 'ž' => "Z",
+        '‘' | '’' | '‚' | '‛' | '`' | '´' => "'",
+        '“' | '”' | '„' => "\"",
+        '‐' | '‑' | '‒' | '–' | '—' => "-",
+        '…' => "...",
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// Shortens text longer than `size` characters along with the policy that took effect,
+/// truncate when no abbreviation applies. None when it fits or the policy rejects it.
+/// Abbreviations follow the case of the words they replace and are never cut short
+pub fn fit_text(i: &str, size: usize, policy: OverflowPolicy) -> Option<(String, OverflowPolicy)> {
+    let fits = |i: &str| i.chars().count().le(&size);
+    if fits(i) || policy.eq(&OverflowPolicy::Reject) {
+        return None;
+    }
+
+    let mut res = i.to_owned();
+    if policy.eq(&OverflowPolicy::Abbreviate) {
+        for (re, abbreviation) in ABBREVIATIONS.iter() {
+            if fits(&res) {
+                break;
+            }
+            res = re
+                .replace_all(&res, |caps: &regex::Captures| {
+                    let words = &caps[0][..caps[0].len() - caps[1].len()];
+                    format!("{}{}", match_case(abbreviation, words), &caps[1])
+                })
+                .into_owned();
+        }
+    }
+
+    let applied = match res.eq(i) {
+        true => OverflowPolicy::Truncate,
+        false => OverflowPolicy::Abbreviate,
+    };
+    let mut shortened: String = res.chars().take(size).collect();
+
+    // An abbreviation cut short reads as another word, leave it out instead
+    let tail = shortened.rsplit(' ').next().unwrap_or_default();
+    let cut = res[shortened.len()..].split(' ').next().unwrap_or_default();
+    let cut_abbreviation = ABBREVIATIONS
+        .iter()
+        .any(|(_, abbreviation)| abbreviation.eq_ignore_ascii_case(&format!("{}{}", tail, cut)));
+    if applied.eq(&OverflowPolicy::Abbreviate)
+        && !cut.is_empty()
+        && cut_abbreviation
+        && tail.len().lt(&shortened.trim_start().len())
+    {
+        shortened.truncate(shortened.len() - tail.len());
+    }
+
+    Some((shortened.trim_end().to_owned(), applied))
+}
+
+/// Lowercase words get a lowercase abbreviation and capitalised words a capitalised one,
+/// except initialisms of several words, e.g. Pty Ltd to PL
+fn match_case(abbreviation: &str, words: &str) -> String {
+    let has_lowercase = words.chars().any(|c| c.is_lowercase());
+    let has_uppercase = words.chars().any(|c| c.is_uppercase());
+
+    match (has_lowercase, has_uppercase) {
+        (false, _) => abbreviation.to_owned(),
+        (true, false) => abbreviation.to_lowercase(),
+        (true, true) if words.contains(char::is_whitespace) => abbreviation.to_owned(),
+        (true, true) => {
+            let (first, rest) = abbreviation.split_at(1usize);
+            format!("{}{}", first, rest.to_lowercase())
+        }
+    }
+}
+
 pub fn validate_csv_tax_withhold(i: &str, res: &mut Vec<&str>) -> bool {
     if i.replace(".", "").len().gt(&8usize) || !validate_number(&i.replace(".", "")) {
         res.push("Tax withold field must be digits and must not exceed 8 digits");
@@ -256,6 +519,22 @@ pub fn validate_csv_tax_withhold(i: &str, res: &mut Vec<&str>) -> bool {
     }
 }
 
+pub fn validate_csv_trans_code(i: &str, res: &mut Vec<&str>) -> bool {
+    if !TRANS_CODE.contains(&i) {
+        res.push("Transaction code field must be one of 13, 50, 51, 52, 53, 54, 55, 56, 57");
+        return false;
+    }
+    true
+}
+
+pub fn validate_csv_indicator(i: &str, res: &mut Vec<&str>) -> bool {
+    if !INDICATOR.contains(&i) {
+        res.push("Indicator field must be one of N, W, X, Y, or blank");
+        return false;
+    }
+    true
+}
+
 #[test]
 fn test_normalise_amount() {
     let i = "123.45";
@@ -292,9 +571,9 @@ fn test_validate_csv_bank_name() {
     let too_long: &str = "lolol";
     let non_exist: &str = "lol";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_bank_name(blank, &mut res), false);
-    assert_eq!(validate_csv_bank_name(too_long, &mut res), false);
-    assert_eq!(validate_csv_bank_name(non_exist, &mut res), false)
+    assert!(!validate_csv_bank_name(blank, &mut res));
+    assert!(!validate_csv_bank_name(too_long, &mut res));
+    assert!(!validate_csv_bank_name(non_exist, &mut res))
 }
 
 #[test]
@@ -302,8 +581,8 @@ fn test_validate_csv_user_name() {
     let blank: &str = "";
     let too_long: &str = "lololololololololololololol";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_user_name(blank, &mut res), false);
-    assert_eq!(validate_csv_user_name(too_long, &mut res), false)
+    assert!(!validate_csv_user_name(blank, &mut res));
+    assert!(!validate_csv_user_name(too_long, &mut res))
 }
 
 #[test]
@@ -311,8 +590,8 @@ fn test_validate_csv_apca_number() {
     let non_digits: &str = "lololo";
     let too_long: &str = "1234567";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_apca_number(non_digits, &mut res), false);
-    assert_eq!(validate_csv_apca_number(too_long, &mut res), false)
+    assert!(!validate_csv_apca_number(non_digits, &mut res));
+    assert!(!validate_csv_apca_number(too_long, &mut res))
 }
 
 #[test]
@@ -320,8 +599,8 @@ fn test_validate_csv_file_description() {
     let blank: &str = "";
     let too_long: &str = "lolololololol";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_file_description(blank, &mut res), false);
-    assert_eq!(validate_csv_file_description(too_long, &mut res), false)
+    assert!(!validate_csv_file_description(blank, &mut res));
+    assert!(!validate_csv_file_description(too_long, &mut res))
 }
 
 #[test]
@@ -330,9 +609,9 @@ fn test_validate_csv_settle_date() {
     let too_long: &str = "1111111";
     let non_date: &str = "300220";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_settle_date(blank, &mut res), false);
-    assert_eq!(validate_csv_settle_date(too_long, &mut res), false);
-    assert_eq!(validate_csv_settle_date(non_date, &mut res), false)
+    assert!(!validate_csv_settle_date(blank, &mut res));
+    assert!(!validate_csv_settle_date(too_long, &mut res));
+    assert!(!validate_csv_settle_date(non_date, &mut res))
 }
 
 #[test]
@@ -345,34 +624,24 @@ fn test_validate_csv_trace() {
     let too_long_trace: &str = "lolololololololol";
     let too_long_client: &str = "lolololololololololololololololol";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_bsb(blank, &mut res, BsbType::DetailBsb), false);
-    assert_eq!(
-        validate_bsb(invalid_bsb, &mut res, BsbType::DetailBsb),
-        false
-    );
-    assert_eq!(
-        validate_bsb(too_longbsb, &mut res, BsbType::DetailBsb),
-        false
-    );
-    assert_eq!(
-        validate_account_number(blank, &mut res, BsbType::DetailBsb),
-        false
-    );
-    assert_eq!(
-        validate_account_number(acct, &mut res, BsbType::DetailBsb),
-        false
-    );
-    assert_eq!(
-        validate_account_number(bad_acct, &mut res, BsbType::DetailBsb),
-        false
-    );
-    assert_eq!(validate_csv_trace_account_name(blank, &mut res), false);
-    assert_eq!(
-        validate_csv_trace_account_name(too_long_trace, &mut res),
-        false
-    );
-    assert_eq!(validate_csv_client_name(blank, &mut res), false);
-    assert_eq!(validate_csv_client_name(too_long_client, &mut res), false);
+    assert!(!validate_bsb(blank, &mut res, BsbType::DetailBsb));
+    assert!(!validate_bsb(invalid_bsb, &mut res, BsbType::DetailBsb));
+    assert!(!validate_bsb(too_longbsb, &mut res, BsbType::DetailBsb));
+    assert!(!validate_account_number(
+        blank,
+        &mut res,
+        BsbType::DetailBsb
+    ));
+    assert!(!validate_account_number(acct, &mut res, BsbType::DetailBsb));
+    assert!(!validate_account_number(
+        bad_acct,
+        &mut res,
+        BsbType::DetailBsb
+    ));
+    assert!(!validate_csv_trace_account_name(blank, &mut res));
+    assert!(!validate_csv_trace_account_name(too_long_trace, &mut res));
+    assert!(!validate_csv_client_name(blank, &mut res));
+    assert!(!validate_csv_client_name(too_long_client, &mut res));
 }
 
 #[test]
@@ -382,10 +651,10 @@ fn test_validate_csv_amount() {
     let non_digits: &str = "lol";
     let three_decimal: &str = "123.456";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_amount(blank, &mut res), false);
-    assert_eq!(validate_csv_amount(too_long, &mut res), false);
-    assert_eq!(validate_csv_amount(non_digits, &mut res), false);
-    assert_eq!(validate_csv_amount(three_decimal, &mut res), false);
+    assert!(!validate_csv_amount(blank, &mut res));
+    assert!(!validate_csv_amount(too_long, &mut res));
+    assert!(!validate_csv_amount(non_digits, &mut res));
+    assert!(!validate_csv_amount(three_decimal, &mut res));
 }
 
 #[test]
@@ -394,9 +663,9 @@ fn test_validate_csv_comment() {
     let start_with_zero: &str = "0lol";
     let too_long: &str = "lololololololololol";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_comment(start_with_hyphen, &mut res), false);
-    assert_eq!(validate_csv_comment(start_with_zero, &mut res), false);
-    assert_eq!(validate_csv_comment(too_long, &mut res), false)
+    assert!(!validate_csv_comment(start_with_hyphen, &mut res));
+    assert!(!validate_csv_comment(start_with_zero, &mut res));
+    assert!(!validate_csv_comment(too_long, &mut res))
 }
 
 #[test]
@@ -405,7 +674,89 @@ fn test_validate_csv_tax_withhold() {
     let three_decimal: &str = "123.456";
     let too_long: &str = "111111111";
     let mut res: Vec<&str> = Vec::new();
-    assert_eq!(validate_csv_tax_withhold(non_digits, &mut res), false);
-    assert_eq!(validate_csv_tax_withhold(three_decimal, &mut res), false);
-    assert_eq!(validate_csv_tax_withhold(too_long, &mut res), false)
+    assert!(!validate_csv_tax_withhold(non_digits, &mut res));
+    assert!(!validate_csv_tax_withhold(three_decimal, &mut res));
+    assert!(!validate_csv_tax_withhold(too_long, &mut res))
+}
+
+#[test]
+fn test_validate_csv_trans_code_indicator() {
+    let mut res: Vec<&str> = Vec::new();
+    assert!(validate_csv_trans_code("56", &mut res));
+    assert!(!validate_csv_trans_code("58", &mut res));
+    assert!(validate_csv_indicator(" ", &mut res));
+    assert!(!validate_csv_indicator("Z", &mut res));
+}
+
+#[test]
+fn test_normalise_becs() {
+    assert_eq!(normalise_becs("José Müller"), "JOSE MULLER");
+    assert_eq!(normalise_becs("O’Brien – Ævar 😀 Co"), "O'BRIEN - AEVAR CO");
+    assert_eq!(normalise_becs("ŁÓDŹ\u{a0}~pty"), "LODZ PTY");
+    let mut res: Vec<&str> = Vec::new();
+    assert!(validate_csv_becs(
+        "O'BRIEN & CO",
+        &mut res,
+        ValidationType::DetailClientName
+    ));
+    assert!(!validate_csv_becs(
+        "Zoë",
+        &mut res,
+        ValidationType::DetailClientName
+    ));
+}
+
+#[test]
+fn test_fit_text() {
+    let name = "ACME WIDGETS AUSTRALIA PTY LIMITED";
+    assert_eq!(fit_text(name, 32usize, OverflowPolicy::Reject), None);
+    assert_eq!(fit_text("ACME", 32usize, OverflowPolicy::Truncate), None);
+    assert_eq!(
+        fit_text(name, 32usize, OverflowPolicy::Truncate).unwrap(),
+        (
+            "ACME WIDGETS AUSTRALIA PTY LIMIT".to_owned(),
+            OverflowPolicy::Truncate
+        )
+    );
+    assert_eq!(
+        fit_text(name, 32usize, OverflowPolicy::Abbreviate).unwrap(),
+        (
+            "ACME WIDGETS AUSTRALIA PL".to_owned(),
+            OverflowPolicy::Abbreviate
+        )
+    );
+    assert_eq!(
+        fit_text(
+            "Smith Superannuation Fund Pty Ltd",
+            18usize,
+            OverflowPolicy::Abbreviate
+        )
+        .unwrap(),
+        ("Smith Super Fund".to_owned(), OverflowPolicy::Abbreviate)
+    );
+    assert_eq!(
+        fit_text("acme company limited", 12usize, OverflowPolicy::Abbreviate).unwrap(),
+        ("acme co ltd".to_owned(), OverflowPolicy::Abbreviate)
+    );
+    assert_eq!(
+        fit_text(
+            "Acme Pty Ltd as trustee for the Smith Family Trust",
+            32usize,
+            OverflowPolicy::Abbreviate
+        )
+        .unwrap(),
+        (
+            "Acme PL atf the Smith Family Tru".to_owned(),
+            OverflowPolicy::Abbreviate
+        )
+    );
+    assert_eq!(
+        fit_text(
+            "INVOICE 2024-000123 MARCH",
+            18usize,
+            OverflowPolicy::Abbreviate
+        )
+        .unwrap(),
+        ("INVOICE 2024-00012".to_owned(), OverflowPolicy::Truncate)
+    );
 }
@@ -1,10 +1,55 @@
 //! Error types used for .aba file validation
 use thiserror::Error;
 
+use crate::report::*;
+
+/// Top level error returned from every public entry point of the library
+#[non_exhaustive]
+#[derive(Error, Debug)]
+pub enum CemtexerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid csv file format, most likely a missing comma to denote a field, please refer to self integration guide: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Invalid json file format, please refer to the json layout in the self integration guide: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Template error: {0}")]
+    Template(String),
+    #[error("Format error: {0}")]
+    Format(String),
+    #[error("The follow error(s) are detected:\n{}", .0.join("\n"))]
+    Validation(Vec<String>),
+}
+
+impl CemtexerError {
+    /// Process exit status for the command line, 1 for validation failures and
+    /// 2 when the input could not be read or is structurally malformed
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CemtexerError::Validation(_) => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl From<LineParseError> for CemtexerError {
+    fn from(e: LineParseError) -> Self {
+        CemtexerError::Validation(vec![e.to_string()])
+    }
+}
+
 /// Pre-filled error messages for line counts and data fields which are fixed in the blocks structures
 #[non_exhaustive]
 #[derive(Error, Debug, Clone)]
 pub enum LineParseError {
+    #[error("- At line 1 in the descriptive block the character count is {0}, but in order to form a valid line it must be exactly 120 characters\n")]
+    DescriptiveLineLength(usize),
+    #[error(
+        "- At line 1 in the descriptive block, it must only contain ASCII characters, not {0}\n"
+    )]
+    DescriptiveNonAscii(String),
+    #[error("- At line 1 in the descriptive block the character count is {0}, the blanks between character position {} - 120 were restored\n", .0 + 1)]
+    DescriptivePaddingRepaired(usize),
     #[error(
         "- At line 1 in the descriptive block at character position 1, it must be 0 not {0}\n"
     )]
@@ -15,18 +60,34 @@ pub enum LineParseError {
     DescriptiveReelSequence(String),
     #[error("- At line 1 in the descriptive block between character position 21 - 23, it must contain valid 3 character bank code\n")]
     DescriptiveUserName,
+    #[error("- At line 1 in the descriptive block between character position 21 - 23, the bank name {0} does not own the trace BSB {1} at line {2}, which belongs to {3}, use {3} instead\n")]
+    DescriptiveBankTraceBsb(String, String, u32, String),
+    #[error("- At line 1 in the descriptive block between character position 21 - 23, the bank name {0} may not own the trace BSB {1} at line {2}, which is in the BSB range of {3}, import the BSB directory to confirm its institution\n")]
+    DescriptiveBankTraceBsbGuessed(String, String, u32, String),
     #[error("- At line 1 in the descriptive block between character position 24 - 30, all must be 7 blanks\n")]
     DescriptiveBlankTwo,
     #[error("- At line 1 in the descriptive block between character position 31 - 56, it must be left adjusted and must not be all blank\n")]
     DescriptiveSelfName,
+    #[error("- At line 1 in the descriptive block between character position 31 - 56, it must only contain BECS characters, not {0}\n")]
+    DescriptiveSelfNameCharset(String),
     #[error("- At line 1 in the descriptive block between character position 57 - 62, all must be numerics\n")]
     DescriptiveApca,
     #[error("- At line 1 in the descriptive block between character position 63 - 74, it must be left adjusted and must not be all blank\n")]
     DescriptiveFileEntry,
+    #[error("- At line 1 in the descriptive block between character position 63 - 74, it must only contain BECS characters, not {0}\n")]
+    DescriptiveFileEntryCharset(String),
     #[error("- At line 1 in the descriptive block between character position 75 - 80, the date format must be in DDMMYY\n")]
     DescriptiveDate,
+    #[error("- At line 1 in the descriptive block between character position 75 - 80, the settlement date {0} falls on {1}, which is not a processing day, use {2} instead\n")]
+    DescriptiveSettleDateClosed(String, String, String),
+    #[error("- At line 1 in the descriptive block between character position 75 - 80, the settlement date {0} is in the past, use {1} instead\n")]
+    DescriptiveSettleDatePast(String, String),
     #[error("- At line 1 in the descriptive block between character position 81 - 120, all must be 40 blanks\n")]
     DescriptiveBlankThree,
+    #[error("- At line {0} in the detail block the character count is {1}, but in order to form a valid line it must be exactly 120 characters\n")]
+    DetailLineLength(u32, usize),
+    #[error("- At line {0} in the detail block, it must only contain ASCII characters, not {1}\n")]
+    DetailNonAscii(u32, String),
     #[error("- At line {0} in the detail block at character position 1, it must be 1 not {1}\n")]
     DetailRecordTypeOne(u32, String),
     #[error("- At line {0} in the detail block between character position 2 - 7, it must contain valid BSB number, but you have {1}\n")]
@@ -41,16 +102,30 @@ pub enum LineParseError {
     DetailAmount(u32),
     #[error("- At line {0} in the detail block between character position 31 -62, the name must be left justified\n")]
     DetailClientName(u32),
+    #[error("- At line {0} in the detail block between character position 31 - 62, it must only contain BECS characters, not {1}\n")]
+    DetailClientNameCharset(u32, String),
     #[error("- At line {0} in the detail block between character position 63 - 80, the lodge reference must be left justifed and must not start zeros and hyphens\n")]
     DetailLodgeRef(u32),
+    #[error("- At line {0} in the detail block between character position 63 - 80, it must only contain BECS characters, not {1}\n")]
+    DetailLodgeRefCharset(u32, String),
     #[error("- At line {0} in the detail block between character position 81 - 87, it must contain valid BSB number, but you have {1}\n")]
     DetailBsbTrace(u32, String),
     #[error("- At line {0} in the detail block between character position 88 - 96, the account number must be right justified\n")]
     DetailSrcAccount(u32),
     #[error("- At line {0} in the detail block between character position 97 - 112, the name must be left justified and must not be all blank\n")]
     DetailRemitter(u32),
+    #[error("- At line {0} in the detail block between character position 97 - 112, it must only contain BECS characters, not {1}\n")]
+    DetailRemitterCharset(u32, String),
     #[error("- At line {0} in the detail block between character position 113 - 120, the amount must be right justified\n")]
     DetailTaxWithhold(u32),
+    #[error("- At the last line in the total block the character count is {0}, but in order to form a valid line it must be exactly 120 characters\n")]
+    TotalLineLength(usize),
+    #[error(
+        "- At the last line in the total block, it must only contain ASCII characters, not {0}\n"
+    )]
+    TotalNonAscii(String),
+    #[error("- At the last line in the total block the character count is {0}, the blanks between character position {} - 120 were restored\n", .0 + 1)]
+    TotalPaddingRepaired(usize),
     #[error(
         "- At the last line in the total block at character position 1, it must be 7 not {0}\n"
     )]
@@ -65,10 +140,22 @@ pub enum LineParseError {
     TotalCompute,
     #[error("- At the last line in the total block between character position 21 - 30, the credit/debit field failed validation, see next error(s)\n")]
     TotalMalformedCreditDebit,
+    #[error("- At the last line in the total block between character position 21 - 30, the net total must be zero for a self-balancing file\n")]
+    TotalNotBalanced,
+    #[error("- At the last line in the total block between character position 21 - 30, the net total ${0} is not equal to the net of the detail amounts ${1}\n")]
+    TotalNetMismatch(String, String),
     #[error("- At the last line in the total block between character position 31 - 40, all must be numerics\n")]
     TotalCredit,
+    #[error("- At the last line in the total block between character position 31 - 40, the credit total ${0} is not equal to the sum of credit detail amounts ${1}\n")]
+    TotalCreditMismatch(String, String),
+    #[error("- At the last line in the total block between character position 31 - 40, the sum of credit detail amounts exceeds $99999999.99 and does not fit the field\n")]
+    TotalCreditOverflow,
     #[error("- At the last line in the total block between character position 41 - 50, all must be numerics\n")]
     TotalDebit,
+    #[error("- At the last line in the total block between character position 41 - 50, the debit total ${0} is not equal to the sum of debit detail amounts ${1}\n")]
+    TotalDebitMismatch(String, String),
+    #[error("- At the last line in the total block between character position 41 - 50, the sum of debit detail amounts exceeds $99999999.99 and does not fit the field\n")]
+    TotalDebitOverflow,
     #[error("- At the last line in the total block between character position 51 - 74, all must be 24 blanks\n")]
     TotalBlankTwo,
     #[error("- At the last line in the total block between character position 75 - 80, all must be numerics\n")]
@@ -78,3 +165,94 @@ pub enum LineParseError {
     #[error("- At the last line in the total block between character position 81 - 120, all must be 40 blanks\n")]
     TotalBlankThree,
 }
+
+impl LineParseError {
+    /// Stable rule code, block field name and 1-based inclusive column span of the field at fault
+    fn meta(&self) -> (&'static str, &'static str, u32, u32) {
+        match self {
+            LineParseError::DescriptiveLineLength(_) => ("ABA100", "line", 1, 120),
+            LineParseError::DescriptiveRecordTypeZero(_) => ("ABA101", "record_type", 1, 1),
+            LineParseError::DescriptiveBlankOne => ("ABA102", "blank_1", 2, 18),
+            LineParseError::DescriptiveReelSequence(_) => ("ABA103", "reel_seq", 19, 20),
+            LineParseError::DescriptiveUserName => ("ABA104", "bank_name", 21, 23),
+            LineParseError::DescriptiveBlankTwo => ("ABA105", "blank_2", 24, 30),
+            LineParseError::DescriptiveSelfName => ("ABA106", "user_name", 31, 56),
+            LineParseError::DescriptiveApca => ("ABA107", "apca_number", 57, 62),
+            LineParseError::DescriptiveFileEntry => ("ABA108", "file_description", 63, 74),
+            LineParseError::DescriptiveDate => ("ABA109", "settle_date", 75, 80),
+            LineParseError::DescriptiveSettleDateClosed(..) => ("ABA112", "settle_date", 75, 80),
+            LineParseError::DescriptiveSettleDatePast(..) => ("ABA113", "settle_date", 75, 80),
+            LineParseError::DescriptiveBlankThree => ("ABA110", "blank_3", 81, 120),
+            LineParseError::DescriptiveBankTraceBsb(..) => ("ABA111", "bank_name", 21, 23),
+            LineParseError::DescriptiveSelfNameCharset(_) => ("ABA114", "user_name", 31, 56),
+            LineParseError::DescriptiveFileEntryCharset(_) => {
+                ("ABA115", "file_description", 63, 74)
+            }
+            LineParseError::DescriptiveNonAscii(_) => ("ABA116", "line", 1, 120),
+            LineParseError::DescriptivePaddingRepaired(_) => ("ABA117", "line", 1, 120),
+            LineParseError::DescriptiveBankTraceBsbGuessed(..) => ("ABA118", "bank_name", 21, 23),
+            LineParseError::DetailLineLength(..) => ("ABA200", "line", 1, 120),
+            LineParseError::DetailRecordTypeOne(..) => ("ABA201", "record_type", 1, 1),
+            LineParseError::DetailBsbClient(..) => ("ABA202", "bsb_number", 2, 8),
+            LineParseError::DetailDestAccount(_) => ("ABA203", "dest_acct", 9, 17),
+            LineParseError::DetailIndicator(_) => ("ABA204", "indicator", 18, 18),
+            LineParseError::DetailTransCode(_) => ("ABA205", "trans_code", 19, 20),
+            LineParseError::DetailAmount(_) => ("ABA206", "amount", 21, 30),
+            LineParseError::DetailClientName(_) => ("ABA207", "client_name", 31, 62),
+            LineParseError::DetailLodgeRef(_) => ("ABA208", "lodge_ref", 63, 80),
+            LineParseError::DetailBsbTrace(..) => ("ABA209", "trace_bsb", 81, 87),
+            LineParseError::DetailSrcAccount(_) => ("ABA210", "src_acct", 88, 96),
+            LineParseError::DetailRemitter(_) => ("ABA211", "account_name", 97, 112),
+            LineParseError::DetailTaxWithhold(_) => ("ABA212", "tax_withhold", 113, 120),
+            LineParseError::DetailClientNameCharset(..) => ("ABA213", "client_name", 31, 62),
+            LineParseError::DetailLodgeRefCharset(..) => ("ABA214", "lodge_ref", 63, 80),
+            LineParseError::DetailRemitterCharset(..) => ("ABA215", "account_name", 97, 112),
+            LineParseError::DetailNonAscii(..) => ("ABA216", "line", 1, 120),
+            LineParseError::TotalLineLength(_) => ("ABA300", "line", 1, 120),
+            LineParseError::TotalTypeSeven(_) => ("ABA301", "record_type", 1, 1),
+            LineParseError::TotalBsbFiller(_) => ("ABA302", "bsb_filler", 2, 8),
+            LineParseError::TotalBlankOne => ("ABA303", "blank_1", 9, 20),
+            LineParseError::TotalField => ("ABA304", "total", 21, 30),
+            LineParseError::TotalCompute => ("ABA305", "total", 21, 30),
+            LineParseError::TotalMalformedCreditDebit => ("ABA306", "total", 21, 30),
+            LineParseError::TotalCredit => ("ABA307", "credit", 31, 40),
+            LineParseError::TotalDebit => ("ABA308", "debit", 41, 50),
+            LineParseError::TotalBlankTwo => ("ABA309", "blank_2", 51, 74),
+            LineParseError::TotalNonNumeric => ("ABA310", "record_count", 75, 80),
+            LineParseError::TotalCount(..) => ("ABA311", "record_count", 75, 80),
+            LineParseError::TotalBlankThree => ("ABA312", "blank_3", 81, 120),
+            LineParseError::TotalNotBalanced => ("ABA313", "total", 21, 30),
+            LineParseError::TotalNetMismatch(..) => ("ABA314", "total", 21, 30),
+            LineParseError::TotalCreditMismatch(..) => ("ABA315", "credit", 31, 40),
+            LineParseError::TotalDebitMismatch(..) => ("ABA316", "debit", 41, 50),
+            LineParseError::TotalNonAscii(_) => ("ABA317", "line", 1, 120),
+            LineParseError::TotalPaddingRepaired(_) => ("ABA318", "line", 1, 120),
+            LineParseError::TotalCreditOverflow => ("ABA319", "credit", 31, 40),
+            LineParseError::TotalDebitOverflow => ("ABA320", "debit", 41, 50),
+        }
+    }
+
+    /// Stable rule code, `ABA1xx` for descriptive, `ABA2xx` for detail and `ABA3xx` for total block
+    pub fn rule(&self) -> &'static str {
+        self.meta().0
+    }
+
+    /// Name of the block field at fault
+    pub fn field(&self) -> &'static str {
+        self.meta().1
+    }
+
+    /// 1-based inclusive column span of the field at fault
+    pub fn columns(&self) -> (u32, u32) {
+        let (_, _, start, end) = self.meta();
+        (start, end)
+    }
+
+    pub fn block(&self) -> BlockKind {
+        match self.rule().as_bytes()[3] {
+            b'1' => BlockKind::Descriptive,
+            b'2' => BlockKind::Detail,
+            _ => BlockKind::Total,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Automatic repair of common .aba defects, see `Cemtex::fix`
+use std::fmt::Display;
+
+use crate::cemtex::*;
+use crate::errors::*;
+use crate::types::*;
+
+/// A line rewritten by the fix, line numbers are 1-based
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct LineChange {
+    pub line: u32,
+    pub before: String,
+    pub after: String,
+}
+
+/// Fixed content of a .aba file and every line that was changed
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct FixReport {
+    pub content: String,
+    pub changes: Vec<LineChange>,
+}
+
+impl FixReport {
+    /// Parses `content` leniently so that mis-sized lines are fixed as well, the original
+    /// line ending style is kept
+    pub async fn new(content: &str) -> Result<Self, CemtexerError> {
+        let cemtex = Cemtex::from_str_with_mode(content, ParseMode::Lenient)?;
+        let fixed = cemtex.fix().await?;
+        let ending = match content.contains("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+
+        let original: Vec<&str> = content.lines().collect();
+        let changes = (0usize..original.len().max(fixed.len()))
+            .filter_map(|idx| {
+                let before = original.get(idx).copied().unwrap_or_default();
+                let after = fixed.get(idx).map(String::as_str).unwrap_or_default();
+                match before.eq(after) {
+                    true => None,
+                    false => Some(LineChange {
+                        line: idx as u32 + 1u32,
+                        before: before.to_owned(),
+                        after: after.to_owned(),
+                    }),
+                }
+            })
+            .collect();
+
+        let mut content = fixed.join(ending);
+        content.push_str(ending);
+
+        Ok(Self { content, changes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Unified diff without context lines, `before` and `after` name the files in the headers
+    pub fn diff(&self, before: &str, after: &str) -> String {
+        let mut res = match self.is_empty() {
+            true => return String::new(),
+            false => format!("--- {}\n+++ {}\n", before, after),
+        };
+        for change in self.changes.iter() {
+            res.push_str(&change.to_string());
+        }
+
+        res
+    }
+}
+
+impl Display for LineChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "@@ -{} +{} @@", self.line, self.line)?;
+        if !self.before.is_empty() {
+            writeln!(f, "-{}", self.before)?;
+        }
+        if !self.after.is_empty() {
+            writeln!(f, "+{}", self.after)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_fix_report() {
+    let aba = "0                 07CBA       my name                   1111111004231633  230410\r\n\
+               1012-002157108231 53      1234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\r\n\
+               7000-000            000000200000000020000000000000                        000003                                        \r\n";
+    let fixed = FixReport::new(aba).await.unwrap();
+    let lines: Vec<u32> = fixed.changes.iter().map(|c| c.line).collect();
+    assert_eq!(lines, vec![1, 2, 3]);
+    assert!(fixed
+        .content
+        .ends_with("000001                                        \r\n"));
+    assert!(fixed
+        .diff("a.aba", "b.aba")
+        .starts_with("--- a.aba\n+++ b.aba\n@@ -1 +1 @@\n-0 "));
+
+    let report = fixed
+        .content
+        .parse::<Cemtex>()
+        .unwrap()
+        .validate(&crate::context::ValidationContext::new(
+            time::macros::date!(2024 - 10 - 01),
+        ))
+        .await
+        .unwrap();
+    assert!(!report.has_errors());
+    assert!(FixReport::new(&fixed.content).await.unwrap().is_empty());
+}
@@ -1,9 +1,64 @@
 //! Helper structs to assist deserialising common data types
 use nom::{bytes::complete::take, IResult};
-use rand::Rng;
 use std::fmt::Display;
+use time::{Date, Month};
 
-/// BSB number struct to represent XXX-XXX where Xs are numerics
+use crate::errors::*;
+use crate::model::digits;
+
+/// Maps two-digit years onto the 100 consecutive years starting at `start`,
+/// 2000 - 2099 by default
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CenturyWindow {
+    start: i32,
+}
+
+impl Default for CenturyWindow {
+    fn default() -> Self {
+        Self { start: 2000i32 }
+    }
+}
+
+impl CenturyWindow {
+    /// The window must fit in the years 1000 - 9999 of four digits
+    pub fn new(start: i32) -> Result<Self, CemtexerError> {
+        if !(1000i32..=9900i32).contains(&start) {
+            return Err(CemtexerError::Template(format!(
+                "The century window must start between 1000 and 9900, not {}",
+                start
+            )));
+        }
+        Ok(Self { start })
+    }
+
+    pub fn start(&self) -> i32 {
+        self.start
+    }
+
+    pub fn end(&self) -> i32 {
+        self.start + 99i32
+    }
+
+    /// The only year of the window ending with the two digits
+    pub fn year(&self, yy: u8) -> i32 {
+        self.start + (i32::from(yy) - self.start).rem_euclid(100i32)
+    }
+
+    /// Calendar date of a DDMMYY string, `None` for a malformed or non-existent date
+    pub fn date(&self, ddmmyy: &str) -> Option<Date> {
+        let (_, date) = TwoDigitYears::deserialise(ddmmyy).ok()?;
+        date.to_date(self)
+    }
+}
+
+impl Display for CenturyWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {}", self.start, self.end())
+    }
+}
+
+/// BSB number struct to represent XXX-XXX where Xs are numerics.
+/// Kept for compatibility, `model::Bsb` also validates the digits
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct Bsb {
     pub first: String,
@@ -34,8 +89,8 @@ impl Display for Bsb {
 }
 
 /// The .aba file uses non-standard date format of DDMMYY.
-/// Caveat is that the leap year validation is capped at year 9999.
-/// This is a workaround that uses random two digits to make up a 4-digit year.
+/// The century is resolved by a `CenturyWindow`, so the same date always
+/// validates the same way, including 29 February.
 /// Please refer to `<https://www.ietf.org/rfc/rfc3339.txt>` for more info
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
 pub struct TwoDigitYears {
@@ -45,24 +100,26 @@ pub struct TwoDigitYears {
 }
 
 impl TwoDigitYears {
-    pub fn deserialise(i: &str) -> IResult<&str, String> {
-        let mut rng = rand::thread_rng();
-
-        let (i, day) = take(2u8)(i)?;
-        let (i, month) = take(2u8)(i)?;
-        let (i, year) = take(2u8)(i)?;
-
-        Ok((
-            i,
-            format!(
-                "{}{}{}{}{}",
-                day,
-                month,
-                rng.gen_range(1i32..=9i32),
-                rng.gen_range(1i32..=9i32),
-                year
-            ),
-        ))
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        let (i, day) = digits(2usize)(i)?;
+        let (i, month) = digits(2usize)(i)?;
+        let (i, year) = digits(2usize)(i)?;
+
+        let date = Self {
+            day: day.to_string(),
+            month: month.to_string(),
+            year: year.to_string(),
+        };
+
+        Ok((i, date))
+    }
+
+    pub fn to_date(&self, window: &CenturyWindow) -> Option<Date> {
+        let day = self.day.parse::<u8>().ok()?;
+        let month = Month::try_from(self.month.parse::<u8>().ok()?).ok()?;
+        let year = window.year(self.year.parse::<u8>().ok()?);
+
+        Date::from_calendar_date(year, month, day).ok()
     }
 }
 
@@ -105,23 +162,45 @@ impl Display for TotalField {
 #[test]
 fn test_bsb() {
     let bsb: &str = "063-000";
-    let (_, result) = Bsb::deserialise(&bsb).unwrap();
+    let (_, result) = Bsb::deserialise(bsb).unwrap();
     assert_eq!(result.first, "063");
 }
 
 #[test]
 fn test_2dy() {
     let date: &str = "280222";
-    let (_, result) = TwoDigitYears::deserialise(&date).unwrap();
-    let (first, second) = result.split_at(4);
-    let (_, last) = second.split_at(2);
-    assert_eq!(first, "2802");
-    assert_eq!(last, "22");
+    let (_, result) = TwoDigitYears::deserialise(date).unwrap();
+    assert_eq!(result.day, "28");
+    assert_eq!(result.year, "22");
+    assert!(TwoDigitYears::deserialise("28 222").is_err());
+
+    let window = CenturyWindow::default();
+    assert_eq!(
+        window.date(date),
+        Date::from_calendar_date(2022i32, Month::February, 28u8).ok()
+    );
+    assert!(window.date("290200").is_some());
+    assert!(window.date("290222").is_none());
+}
+
+#[test]
+fn test_century_window() {
+    let window = CenturyWindow::new(1950i32).unwrap();
+    assert_eq!(window.year(50u8), 1950i32);
+    assert_eq!(window.year(49u8), 2049i32);
+    assert_eq!(window.to_string(), "1950 - 2049");
+    assert_eq!(CenturyWindow::default().year(99u8), 2099i32);
+    // 1900 is not a leap year, 2000 is
+    assert!(CenturyWindow::new(1900i32)
+        .unwrap()
+        .date("290200")
+        .is_none());
+    assert!(CenturyWindow::new(999i32).is_err());
 }
 
 #[test]
 fn test_total_field() {
     let total: &str = "000312924700031292470000000000";
-    let (_, result) = TotalField::deserialise(&total).unwrap();
+    let (_, result) = TotalField::deserialise(total).unwrap();
     assert_eq!(result.total, "0003129247");
 }
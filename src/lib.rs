@@ -3,10 +3,16 @@
 //! Also functions as a parser validator for ABA file
 
 pub mod blocks;
+pub mod bsb;
+pub mod calendar;
 pub mod cemtex;
 pub mod cli;
+pub mod context;
 pub mod csv;
 pub mod errors;
+pub mod fix;
 pub mod helper;
+pub mod model;
 pub mod parser_utils;
+pub mod report;
 pub mod types;
@@ -1,29 +1,46 @@
-#![allow(unused_imports)]
-
-mod blocks;
-mod cemtex;
-mod cli;
-mod csv;
-mod errors;
-mod helper;
-mod parser_utils;
-mod types;
-
 use clap::Parser;
+use std::process::exit;
 
-use cli::*;
+use cemtexer::cli::*;
 
 #[doc(hidden)]
 #[tokio::main]
-async fn main() -> Result<(), std::io::Error> {
+async fn main() {
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::Showtemplate => print_example_template().await?,
-        Commands::Gentemplate { path } => generate_template(path).await?,
-        Commands::Abagen { paths } => aba_gen(paths).await?,
-        Commands::Abacheck { path } => aba_check(path).await?,
-    }
+    // Only the commands that validate read the BSB directory and the holiday calendar
+    let res = match cli.command.clone() {
+        Commands::Showtemplate => print_example_template().await,
+        Commands::Gentemplate { path } => generate_template(path).await,
+        Commands::Abagen { paths } => match validation_context(&cli).await {
+            Ok(ctx) => aba_gen(paths, &ctx).await,
+            Err(e) => Err(e),
+        },
+        Commands::Abacheck { path } => match validation_context(&cli).await {
+            Ok(ctx) => match aba_check(path, &ctx).await {
+                Ok(report) => exit(report.exit_code()),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        },
+        Commands::Abadump { path } => match century_window(&cli) {
+            Ok(window) => aba_dump(path, &window).await,
+            Err(e) => Err(e),
+        },
+        Commands::Abafix { path } => aba_fix(path).await,
+        Commands::Bsb { command } => match command {
+            BsbCommands::Import { path, delta } => bsb_import(path, delta).await,
+            BsbCommands::Lookup { bsb } => match validation_context(&cli).await {
+                Ok(ctx) => bsb_lookup(bsb, &ctx).await,
+                Err(e) => Err(e),
+            },
+            _ => unreachable!("every bsb subcommand is handled"),
+        },
+        _ => unreachable!("every subcommand is handled"),
+    };
 
-    Ok(())
+    if let Err(e) = res {
+        eprintln!("{}\nProgram aborted", e);
+        exit(e.exit_code());
+    }
 }
@@ -0,0 +1,483 @@
+//! Typed data fields shared by the typed .aba records
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::{map, map_res, verify},
+    sequence::separated_pair,
+    IResult,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+use time::Date;
+
+use crate::errors::*;
+use crate::helper::*;
+use crate::parser_utils::*;
+
+/// BSB number in the form of XXX-XXX where Xs are numerics
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Bsb(String);
+
+impl Bsb {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        map(
+            separated_pair(digits(3usize), tag("-"), digits(3usize)),
+            |(first, second)| Self(format!("{}-{}", first, second)),
+        )(i)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Bsb {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Self::deserialise(s) {
+            Ok(("", bsb)) => Ok(bsb),
+            _ => Err(CemtexerError::Format(format!(
+                "`{}` is not a BSB number in the format of xxx-xxx where x are digits",
+                s
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Bsb {
+    type Error = CemtexerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Bsb> for String {
+    fn from(bsb: Bsb) -> Self {
+        bsb.0
+    }
+}
+
+impl Display for Bsb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Account number of up to 9 digits, stored without the blank fill
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AccountNumber(String);
+
+impl AccountNumber {
+    /// Parses the right justified, blank filled 9 character field
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        map_res(take(9u8), |s: &str| s.trim_start_matches(' ').parse())(i)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for AccountNumber {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len().gt(&9usize) || !validate_number(s) {
+            return Err(CemtexerError::Format(format!(
+                "`{}` is not an account number of up to 9 digits",
+                s
+            )));
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl TryFrom<String> for AccountNumber {
+    type Error = CemtexerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<AccountNumber> for String {
+    fn from(acct: AccountNumber) -> Self {
+        acct.0
+    }
+}
+
+impl Display for AccountNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Monetary amount in cents
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize, Deserialize,
+)]
+pub struct Cents(pub u64);
+
+impl Cents {
+    /// Largest amount that fits the 10 digit amount and total fields, $99,999,999.99
+    pub const MAX: Cents = Cents(9_999_999_999u64);
+
+    /// Adds two amounts, None if the result exceeds `Cents::MAX`
+    pub fn checked_add(self, rhs: Cents) -> Option<Cents> {
+        self.0
+            .checked_add(rhs.0)
+            .filter(|sum| sum.le(&Self::MAX.0))
+            .map(Cents)
+    }
+
+    /// Subtracts `rhs`, None if the result would be negative
+    pub fn checked_sub(self, rhs: Cents) -> Option<Cents> {
+        self.0.checked_sub(rhs.0).map(Cents)
+    }
+
+    /// Net amount between two totals regardless of which one is greater
+    pub fn abs_diff(self, rhs: Cents) -> Cents {
+        Cents(self.0.abs_diff(rhs.0))
+    }
+
+    /// Parses a zero filled amount field of `width` digits
+    pub fn deserialise(width: usize) -> impl Fn(&str) -> IResult<&str, Self> {
+        move |i: &str| map_res(digits(width), |s: &str| s.parse::<u64>().map(Cents))(i)
+    }
+
+    /// Reads a complete zero filled field of `width` digits, None when it is malformed
+    pub fn from_field(i: &str, width: usize) -> Option<Self> {
+        match Self::deserialise(width)(i) {
+            Ok(("", cents)) => Some(cents),
+            _ => None,
+        }
+    }
+
+    /// Zero filled representation of `width` digits as used in the fixed width fields
+    pub fn to_field(self, width: usize) -> String {
+        format!("{:0width$}", self.0, width = width)
+    }
+}
+
+/// Parses cent-denoted (e.g. 12345) or two-decimal (e.g. 123.45) amounts,
+/// with or without dollar sign prefix, capped at `Cents::MAX`
+impl FromStr for Cents {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || CemtexerError::Format(format!("`{}` is not a valid amount", s));
+        let i = s.trim().trim_start_matches('$');
+        let digits = match i.split_once('.') {
+            Some((dollars, cents)) if cents.len().eq(&2usize) => format!("{}{}", dollars, cents),
+            Some(_) => return Err(err()),
+            None => i.to_owned(),
+        };
+
+        if digits.is_empty() || !validate_number(&digits) {
+            return Err(err());
+        }
+
+        match digits.trim_start_matches('0') {
+            "" => Ok(Cents(0u64)),
+            significant if significant.len().gt(&10usize) => Err(CemtexerError::Format(format!(
+                "`{}` exceeds the maximum amount of ${}",
+                s,
+                Self::MAX
+            ))),
+            significant => significant.parse::<u64>().map(Cents).map_err(|_| err()),
+        }
+    }
+}
+
+impl Display for Cents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+/// Transaction codes permitted in the detail block
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TransactionCode {
+    /// 13, externally initiated debit
+    Debit,
+    /// 50, externally initiated credit
+    Credit,
+    /// 51, Australian Government security interest
+    GovernmentSecurityInterest,
+    /// 52, family allowance
+    FamilyAllowance,
+    /// 53, pay
+    Pay,
+    /// 54, pension
+    Pension,
+    /// 55, allotment
+    Allotment,
+    /// 56, dividend
+    Dividend,
+    /// 57, debenture or note interest
+    DebentureInterest,
+}
+
+impl TransactionCode {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        map_res(take(2u8), |s: &str| s.parse())(i)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransactionCode::Debit => "13",
+            TransactionCode::Credit => "50",
+            TransactionCode::GovernmentSecurityInterest => "51",
+            TransactionCode::FamilyAllowance => "52",
+            TransactionCode::Pay => "53",
+            TransactionCode::Pension => "54",
+            TransactionCode::Allotment => "55",
+            TransactionCode::Dividend => "56",
+            TransactionCode::DebentureInterest => "57",
+        }
+    }
+
+    pub fn is_debit(&self) -> bool {
+        self.eq(&TransactionCode::Debit)
+    }
+}
+
+impl FromStr for TransactionCode {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "13" => Ok(TransactionCode::Debit),
+            "50" => Ok(TransactionCode::Credit),
+            "51" => Ok(TransactionCode::GovernmentSecurityInterest),
+            "52" => Ok(TransactionCode::FamilyAllowance),
+            "53" => Ok(TransactionCode::Pay),
+            "54" => Ok(TransactionCode::Pension),
+            "55" => Ok(TransactionCode::Allotment),
+            "56" => Ok(TransactionCode::Dividend),
+            "57" => Ok(TransactionCode::DebentureInterest),
+            _ => Err(CemtexerError::Format(format!(
+                "`{}` is not one of the transaction codes {}",
+                s,
+                TRANS_CODE.join(", ")
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for TransactionCode {
+    type Error = CemtexerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<TransactionCode> for String {
+    fn from(code: TransactionCode) -> Self {
+        code.code().to_owned()
+    }
+}
+
+impl Display for TransactionCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Withholding tax indicators, blank for the absolute majority of transactions
+#[non_exhaustive]
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub enum Indicator {
+    #[default]
+    Blank,
+    /// N, new or varied BSB or account number details
+    NewOrVaried,
+    /// W, dividend paid to a resident of a country with a double tax agreement
+    DoubleTaxAgreement,
+    /// X, dividend paid to a non-resident without a double tax agreement
+    NonResidentDividend,
+    /// Y, interest paid to a non-resident
+    NonResidentInterest,
+}
+
+impl Indicator {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        map_res(take(1u8), |s: &str| s.parse())(i)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Indicator::Blank => " ",
+            Indicator::NewOrVaried => "N",
+            Indicator::DoubleTaxAgreement => "W",
+            Indicator::NonResidentDividend => "X",
+            Indicator::NonResidentInterest => "Y",
+        }
+    }
+}
+
+impl FromStr for Indicator {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            " " | "" => Ok(Indicator::Blank),
+            "N" => Ok(Indicator::NewOrVaried),
+            "W" => Ok(Indicator::DoubleTaxAgreement),
+            "X" => Ok(Indicator::NonResidentDividend),
+            "Y" => Ok(Indicator::NonResidentInterest),
+            _ => Err(CemtexerError::Format(format!(
+                "`{}` is not one of the indicators N, W, X, Y or blank",
+                s
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Indicator {
+    type Error = CemtexerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Indicator> for String {
+    fn from(indicator: Indicator) -> Self {
+        indicator.code().to_owned()
+    }
+}
+
+impl Display for Indicator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Settlement date, written as DDMMYY in the descriptive block
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SettleDate(pub Date);
+
+impl SettleDate {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        Self::deserialise_in(CenturyWindow::default())(i)
+    }
+
+    /// Parses the DDMMYY field with its two-digit year in `window`
+    pub fn deserialise_in(window: CenturyWindow) -> impl Fn(&str) -> IResult<&str, Self> {
+        move |i: &str| map_res(take(6u8), |s: &str| Self::parse_in(s, &window))(i)
+    }
+
+    /// Reads a DDMMYY date with its two-digit year in `window`
+    pub fn parse_in(s: &str, window: &CenturyWindow) -> Result<Self, CemtexerError> {
+        match s.len().eq(&6usize) {
+            true => window.date(s),
+            false => None,
+        }
+        .map(SettleDate)
+        .ok_or_else(|| {
+            CemtexerError::Format(format!(
+                "`{}` is not a valid date in DDMMYY for the years {}",
+                s, window
+            ))
+        })
+    }
+}
+
+impl FromStr for SettleDate {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_in(s, &CenturyWindow::default())
+    }
+}
+
+impl TryFrom<String> for SettleDate {
+    type Error = CemtexerError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SettleDate> for String {
+    fn from(date: SettleDate) -> Self {
+        date.to_string()
+    }
+}
+
+impl Display for SettleDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}{:02}{:02}",
+            self.0.day(),
+            u8::from(self.0.month()),
+            self.0.year().rem_euclid(100)
+        )
+    }
+}
+
+/// Takes exactly `n` numerics
+pub fn digits(n: usize) -> impl Fn(&str) -> IResult<&str, &str> {
+    move |i: &str| verify(take(n), |s: &str| validate_number(s))(i)
+}
+
+/// Takes exactly `n` blanks
+pub fn blanks(n: usize) -> impl Fn(&str) -> IResult<&str, &str> {
+    move |i: &str| verify(take(n), |s: &str| s.chars().all(|c| c.eq(&' ')))(i)
+}
+
+#[test]
+fn test_bsb() {
+    let (_, result) = Bsb::deserialise("063-000").unwrap();
+    assert_eq!(result.as_str(), "063-000");
+    assert!("063 000".parse::<Bsb>().is_err());
+}
+
+#[test]
+fn test_typed_fields() {
+    let (_, acct) = AccountNumber::deserialise("   123456").unwrap();
+    assert_eq!(acct.as_str(), "123456");
+    let (_, amount) = Cents::deserialise(10usize)("0000001234").unwrap();
+    assert_eq!(amount.to_string(), "12.34");
+    assert_eq!(amount.to_field(10usize), "0000001234");
+    assert_eq!("$123.45".parse::<Cents>().unwrap(), Cents(12345));
+    assert_eq!("0000000000".parse::<Cents>().unwrap(), Cents(0));
+    assert!("123.4".parse::<Cents>().is_err());
+    assert!("99999999999".parse::<Cents>().is_err());
+    assert_eq!(Cents::from_field("0000012345", 10usize), Some(Cents(12345)));
+    assert_eq!(Cents::from_field("      1234", 10usize), None);
+    assert_eq!(Cents::from_field("0000123.45", 10usize), None);
+    assert_eq!(Cents::MAX.to_string(), "99999999.99");
+    assert!(Cents::MAX.checked_add(Cents(1)).is_none());
+    assert_eq!(Cents(10).abs_diff(Cents(32)), Cents(22));
+    assert!("58".parse::<TransactionCode>().is_err());
+    assert_eq!("N".parse::<Indicator>().unwrap(), Indicator::NewOrVaried);
+
+    let date: SettleDate = "290224".parse().unwrap();
+    assert_eq!(date.to_string(), "290224");
+    assert!("290223".parse::<SettleDate>().is_err());
+}
+
+#[test]
+fn test_settle_date_window() {
+    let window = CenturyWindow::new(1900).unwrap();
+    assert!(SettleDate::parse_in("290200", &window).is_err());
+    assert!(SettleDate::parse_in("290200", &CenturyWindow::default()).is_ok());
+    assert_eq!(
+        SettleDate::parse_in("010299", &window).unwrap().0.year(),
+        1999
+    );
+    assert!(SettleDate::deserialise_in(window)("290200").is_err());
+}
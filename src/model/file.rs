@@ -0,0 +1,108 @@
+//! Typed representation of a complete .aba file, used as the json exchange format
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::errors::*;
+use crate::helper::CenturyWindow;
+use crate::model::*;
+
+/// Descriptive header, detail lines and trailer of a single .aba file
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct AbaFile {
+    pub descriptive: Descriptive,
+    pub details: Vec<Detail>,
+    pub total: Total,
+}
+
+impl AbaFile {
+    /// Reads the json exchange format, failing when a value would not fit its field
+    pub fn from_json(i: &str) -> Result<Self, CemtexerError> {
+        Self::from_json_in(i, &CenturyWindow::default())
+    }
+
+    /// Reads the json exchange format with the two-digit settlement year in `window`
+    pub fn from_json_in(i: &str, window: &CenturyWindow) -> Result<Self, CemtexerError> {
+        let mut file: Self = serde_json::from_str(i)?;
+        // Serde reads the year in the default window, which accepts every date another
+        // window does as 2000 is a leap year, so the date is read again in `window`
+        file.descriptive.settle_date =
+            SettleDate::parse_in(&file.descriptive.settle_date.to_string(), window)?;
+        file.descriptive.check_widths()?;
+        for detail in file.details.iter() {
+            detail.check_widths()?;
+        }
+        file.total.check_widths()?;
+
+        Ok(file)
+    }
+
+    pub fn to_json(&self) -> Result<String, CemtexerError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Writes the fixed width .aba content, one record per line
+impl Display for AbaFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.descriptive)?;
+        for detail in self.details.iter() {
+            writeln!(f, "{}", detail)?;
+        }
+        writeln!(f, "{}", self.total)
+    }
+}
+
+#[test]
+fn test_aba_file_json() {
+    let aba = "0                 01BQL       MY NAME                   1111111004231633  230410                                        \n\
+               1012-002157108231W130000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000000000000001234                        000001                                        \n";
+    let mut lines = aba.lines();
+    let (_, descriptive) = Descriptive::deserialise(lines.next().unwrap()).unwrap();
+    let (_, detail) = Detail::deserialise(lines.next().unwrap()).unwrap();
+    let (_, total) = Total::deserialise(lines.next().unwrap()).unwrap();
+    let file = AbaFile {
+        descriptive,
+        details: vec![detail],
+        total,
+    };
+
+    let json = file.to_json().unwrap();
+    assert!(json.contains("\"settle_date\": \"230410\""));
+    assert!(json.contains("\"amount\": 1234"));
+    assert_eq!(AbaFile::from_json(&json).unwrap(), file);
+    assert_eq!(file.to_string(), aba);
+    assert!(matches!(
+        AbaFile::from_json("{}"),
+        Err(CemtexerError::Json(_))
+    ));
+    assert!(matches!(
+        AbaFile::from_json(&json.replace("MY NAME", &"X".repeat(27))),
+        Err(CemtexerError::Format(_))
+    ));
+}
+
+#[test]
+fn test_aba_file_json_window() {
+    let aba = "0                 01BQL       MY NAME                   1111111004231633  290200                                        \n\
+               1012-002157108231W130000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200\n\
+               7999-999            000000123400000000000000001234                        000001                                        \n";
+    let mut lines = aba.lines();
+    let (_, descriptive) = Descriptive::deserialise(lines.next().unwrap()).unwrap();
+    let (_, detail) = Detail::deserialise(lines.next().unwrap()).unwrap();
+    let (_, total) = Total::deserialise(lines.next().unwrap()).unwrap();
+    let json = AbaFile {
+        descriptive,
+        details: vec![detail],
+        total,
+    }
+    .to_json()
+    .unwrap();
+
+    let window = CenturyWindow::new(1900).unwrap();
+    assert!(matches!(
+        AbaFile::from_json_in(&json, &window),
+        Err(CemtexerError::Format(_))
+    ));
+    assert!(AbaFile::from_json_in(&json, &CenturyWindow::default()).is_ok());
+}
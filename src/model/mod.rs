@@ -0,0 +1,8 @@
+//! Strongly typed counterparts of the .aba blocks
+mod fields;
+mod file;
+mod records;
+
+pub use self::fields::*;
+pub use self::file::*;
+pub use self::records::*;
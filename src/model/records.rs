@@ -0,0 +1,316 @@
+//! Typed descriptive, detail and total records with lossless fixed width conversion
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::map_res,
+    IResult,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::blocks::*;
+use crate::csv::*;
+use crate::errors::*;
+use crate::helper::CenturyWindow;
+use crate::model::*;
+use crate::types::*;
+
+/// Typed counterpart of `DescriptiveBlock`, fillers are implied
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Descriptive {
+    pub reel_seq: u8,
+    pub bank_name: String,
+    pub user_name: String,
+    pub apca_number: u32,
+    pub file_description: String,
+    pub settle_date: SettleDate,
+}
+
+impl Descriptive {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        Self::deserialise_in(CenturyWindow::default())(i)
+    }
+
+    /// Parses the record with the two-digit settlement year in `window`
+    pub fn deserialise_in(window: CenturyWindow) -> impl Fn(&str) -> IResult<&str, Self> {
+        move |i: &str| Self::deserialise_with(i, window)
+    }
+
+    /// Typed counterpart of the block with the two-digit settlement year in `window`
+    pub fn from_block_in(
+        block: &DescriptiveBlock,
+        window: &CenturyWindow,
+    ) -> Result<Self, CemtexerError> {
+        let line = block.to_string();
+        match Self::deserialise_in(*window)(&line) {
+            Ok(("", typed)) => Ok(typed),
+            _ => Err(CemtexerError::Format(format!(
+                "Unable to convert into a typed record, run abacheck for details: {}",
+                line
+            ))),
+        }
+    }
+
+    fn deserialise_with(i: &str, window: CenturyWindow) -> IResult<&str, Self> {
+        let (i, _) = tag("0")(i)?;
+        let (i, _) = blanks(17usize)(i)?;
+        let (i, reel_seq) = map_res(digits(2usize), |s: &str| s.parse::<u8>())(i)?;
+        let (i, bank_name) = take(3u8)(i)?;
+        let (i, _) = blanks(7usize)(i)?;
+        let (i, user_name) = take(26u8)(i)?;
+        let (i, apca_number) = map_res(digits(6usize), |s: &str| s.parse::<u32>())(i)?;
+        let (i, file_description) = take(12u8)(i)?;
+        let (i, settle_date) = SettleDate::deserialise_in(window)(i)?;
+        let (i, _) = blanks(40usize)(i)?;
+
+        let descriptive = Self {
+            reel_seq,
+            bank_name: bank_name.to_owned(),
+            user_name: user_name.trim_end().to_owned(),
+            apca_number,
+            file_description: file_description.trim_end().to_owned(),
+            settle_date,
+        };
+
+        Ok((i, descriptive))
+    }
+
+    /// Fails when a value would not fit its fixed width field
+    pub fn check_widths(&self) -> Result<(), CemtexerError> {
+        fit_width("reel_seq", &self.reel_seq.to_string(), 2usize)?;
+        fit_width("bank_name", &self.bank_name, 3usize)?;
+        fit_width("user_name", &self.user_name, 26usize)?;
+        fit_width("apca_number", &self.apca_number.to_string(), 6usize)?;
+        fit_width("file_description", &self.file_description, 12usize)
+    }
+}
+
+impl Display for Descriptive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "0{}{:02}{}{}{}{:06}{}{}{}",
+            " ".repeat(17),
+            self.reel_seq,
+            left_adjust(&self.bank_name, 3usize, FillStrategy::Blank),
+            " ".repeat(7),
+            left_adjust(&self.user_name, 26usize, FillStrategy::Blank),
+            self.apca_number,
+            left_adjust(&self.file_description, 12usize, FillStrategy::Blank),
+            self.settle_date,
+            " ".repeat(40)
+        )
+    }
+}
+
+/// Typed counterpart of `DetailBlock`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Detail {
+    pub bsb: Bsb,
+    pub account_number: AccountNumber,
+    pub indicator: Indicator,
+    pub trans_code: TransactionCode,
+    pub amount: Cents,
+    pub client_name: String,
+    pub lodge_ref: String,
+    pub trace_bsb: Bsb,
+    pub trace_account_number: AccountNumber,
+    pub remitter: String,
+    pub tax_withhold: Cents,
+}
+
+impl Detail {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        let (i, _) = tag("1")(i)?;
+        let (i, bsb) = Bsb::deserialise(i)?;
+        let (i, account_number) = AccountNumber::deserialise(i)?;
+        let (i, indicator) = Indicator::deserialise(i)?;
+        let (i, trans_code) = TransactionCode::deserialise(i)?;
+        let (i, amount) = Cents::deserialise(10usize)(i)?;
+        let (i, client_name) = take(32u8)(i)?;
+        let (i, lodge_ref) = take(18u8)(i)?;
+        let (i, trace_bsb) = Bsb::deserialise(i)?;
+        let (i, trace_account_number) = AccountNumber::deserialise(i)?;
+        let (i, remitter) = take(16u8)(i)?;
+        let (i, tax_withhold) = Cents::deserialise(8usize)(i)?;
+
+        let detail = Self {
+            bsb,
+            account_number,
+            indicator,
+            trans_code,
+            amount,
+            client_name: client_name.trim_end().to_owned(),
+            lodge_ref: lodge_ref.trim_end().to_owned(),
+            trace_bsb,
+            trace_account_number,
+            remitter: remitter.trim_end().to_owned(),
+            tax_withhold,
+        };
+
+        Ok((i, detail))
+    }
+
+    /// Fails when a value would not fit its fixed width field
+    pub fn check_widths(&self) -> Result<(), CemtexerError> {
+        fit_width("amount", &self.amount.0.to_string(), 10usize)?;
+        fit_width("client_name", &self.client_name, 32usize)?;
+        fit_width("lodge_ref", &self.lodge_ref, 18usize)?;
+        fit_width("remitter", &self.remitter, 16usize)?;
+        fit_width("tax_withhold", &self.tax_withhold.0.to_string(), 8usize)
+    }
+}
+
+impl Display for Detail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "1{}{}{}{}{}{}{}{}{}{}{}",
+            self.bsb,
+            right_adjust(self.account_number.as_str(), 9usize, FillStrategy::Blank),
+            self.indicator,
+            self.trans_code,
+            self.amount.to_field(10usize),
+            left_adjust(&self.client_name, 32usize, FillStrategy::Blank),
+            left_adjust(&self.lodge_ref, 18usize, FillStrategy::Blank),
+            self.trace_bsb,
+            right_adjust(
+                self.trace_account_number.as_str(),
+                9usize,
+                FillStrategy::Blank
+            ),
+            left_adjust(&self.remitter, 16usize, FillStrategy::Blank),
+            self.tax_withhold.to_field(8usize)
+        )
+    }
+}
+
+/// Typed counterpart of `TotalBlock`
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct Total {
+    pub net_total: Cents,
+    pub credit_total: Cents,
+    pub debit_total: Cents,
+    pub record_count: u32,
+}
+
+impl Total {
+    pub fn deserialise(i: &str) -> IResult<&str, Self> {
+        let (i, _) = tag("7999-999")(i)?;
+        let (i, _) = blanks(12usize)(i)?;
+        let (i, net_total) = Cents::deserialise(10usize)(i)?;
+        let (i, credit_total) = Cents::deserialise(10usize)(i)?;
+        let (i, debit_total) = Cents::deserialise(10usize)(i)?;
+        let (i, _) = blanks(24usize)(i)?;
+        let (i, record_count) = map_res(digits(6usize), |s: &str| s.parse::<u32>())(i)?;
+        let (i, _) = blanks(40usize)(i)?;
+
+        let total = Self {
+            net_total,
+            credit_total,
+            debit_total,
+            record_count,
+        };
+
+        Ok((i, total))
+    }
+
+    /// Fails when a value would not fit its fixed width field
+    pub fn check_widths(&self) -> Result<(), CemtexerError> {
+        fit_width("net_total", &self.net_total.0.to_string(), 10usize)?;
+        fit_width("credit_total", &self.credit_total.0.to_string(), 10usize)?;
+        fit_width("debit_total", &self.debit_total.0.to_string(), 10usize)?;
+        fit_width("record_count", &self.record_count.to_string(), 6usize)
+    }
+}
+
+impl Display for Total {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "7999-999{}{}{}{}{}{:06}{}",
+            " ".repeat(12),
+            self.net_total.to_field(10usize),
+            self.credit_total.to_field(10usize),
+            self.debit_total.to_field(10usize),
+            " ".repeat(24),
+            self.record_count,
+            " ".repeat(40)
+        )
+    }
+}
+
+fn fit_width(field: &str, value: &str, width: usize) -> Result<(), CemtexerError> {
+    match value.chars().count().le(&width) {
+        true => Ok(()),
+        false => Err(CemtexerError::Format(format!(
+            "`{}` does not fit the {} character {} field",
+            value, width, field
+        ))),
+    }
+}
+
+macro_rules! block_conversions {
+    ($typed:ty, $block:ty) => {
+        impl TryFrom<&$block> for $typed {
+            type Error = CemtexerError;
+
+            fn try_from(block: &$block) -> Result<Self, Self::Error> {
+                let line = block.to_string();
+                match <$typed>::deserialise(&line) {
+                    Ok(("", typed)) => Ok(typed),
+                    _ => Err(CemtexerError::Format(format!(
+                        "Unable to convert into a typed record, run abacheck for details: {}",
+                        line
+                    ))),
+                }
+            }
+        }
+
+        impl TryFrom<&$typed> for $block {
+            type Error = CemtexerError;
+
+            fn try_from(typed: &$typed) -> Result<Self, Self::Error> {
+                typed.check_widths()?;
+                let line = typed.to_string();
+                match <$block>::deserialise(&line) {
+                    Ok(("", block)) => Ok(block),
+                    _ => Err(CemtexerError::Format(format!(
+                        "Unable to convert into a fixed width record: {}",
+                        line
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+block_conversions!(Descriptive, DescriptiveBlock);
+block_conversions!(Detail, DetailBlock);
+block_conversions!(Total, TotalBlock);
+
+#[test]
+fn test_typed_round_trip() {
+    let desc = "0                 01BQL       MY NAME                   1111111004231633  230410                                        ";
+    let detail = "1123-456157108231 530000001234S R SMITH                       TEST BATCH        062-000 12223123MY ACCOUNT      00001200";
+    let total = "7999-999            000312924700031292470000000000                        000004                                        ";
+
+    let (_, typed) = Descriptive::deserialise(desc).unwrap();
+    assert_eq!(typed.apca_number, 111111);
+    assert_eq!(typed.to_string(), desc);
+
+    let (_, typed) = Detail::deserialise(detail).unwrap();
+    assert_eq!(typed.trans_code, TransactionCode::Pay);
+    assert_eq!(typed.amount, Cents(1234));
+    assert_eq!(typed.to_string(), detail);
+
+    let (_, block) = TotalBlock::deserialise(total).unwrap();
+    let typed = Total::try_from(&block).unwrap();
+    assert_eq!(typed.record_count, 4);
+    assert_eq!(TotalBlock::try_from(&typed).unwrap(), block);
+
+    let overflow = Total {
+        record_count: 1_000_000,
+        ..typed
+    };
+    assert!(TotalBlock::try_from(&overflow).is_err());
+}
@@ -2,17 +2,28 @@
 use lazy_static::lazy_static;
 use nom::character::is_digit;
 use regex::Regex;
-use std::ops::Sub;
-use time::{format_description as Fd, Date as Td};
+use time::Date;
 
+use crate::bsb::*;
+use crate::calendar::*;
 use crate::errors::*;
 use crate::helper::*;
+use crate::model::{Bsb, Cents, SettleDate};
 use crate::types::*;
 
 /// Indicators for transaction types, rarely used in day to day banking unless required
-const INDICATOR: [&str; 5] = ["N", "W", "X", "Y", " "];
+pub const INDICATOR: [&str; 5] = ["N", "W", "X", "Y", " "];
 /// Transaction codes, 53 is used most of the time unless otherwise required
-const TRANS_CODE: [&str; 9] = ["13", "50", "51", "52", "53", "54", "55", "56", "57"];
+pub const TRANS_CODE: [&str; 9] = ["13", "50", "51", "52", "53", "54", "55", "56", "57"];
+/// Transaction code for externally initiated debits, the only debit code in `TRANS_CODE`
+pub const DEBIT_TRANS_CODE: &str = "13";
+/// Transaction code used for generated credits unless otherwise required
+pub const CREDIT_TRANS_CODE: &str = "53";
+/// Transaction code used for a balancing record that credits the trace account
+pub const BALANCING_CREDIT_TRANS_CODE: &str = "50";
+
+/// Symbols of the BECS character set
+pub const BECS_SYMBOLS: &str = "&'()*+,-./:;<=>?@[\\]^_!\"#$%";
 
 lazy_static! {
     static ref RE_BLANK: Regex = Regex::new("^\\s*$").unwrap();
@@ -171,10 +182,14 @@ pub fn validate_file_entry(i: String, fill_type: ValidationType) -> Result<(), L
     }
 }
 
-pub fn validate_date(i: String, fill_type: ValidationType) -> Result<(), LineParseError> {
+pub fn validate_date(
+    i: String,
+    fill_type: ValidationType,
+    window: &CenturyWindow,
+) -> Result<(), LineParseError> {
     match fill_type {
         ValidationType::DescriptiveDate => {
-            if !validate_date_format(&i) {
+            if !validate_date_format_in(&i, window) {
                 Err(LineParseError::DescriptiveDate)
             } else {
                 Ok(())
@@ -188,17 +203,15 @@ pub fn validate_bsb_client(
     i: String,
     fill_type: BsbType,
     line_count: &u32,
+    directory: &BsbDirectory,
 ) -> Result<(), LineParseError> {
-    let (_, bsb) = Bsb::deserialise(&i).unwrap();
-    let file = include_str!("data/bsb");
-
     match fill_type {
         BsbType::DetailBsb => {
-            if !file.contains(&bsb.to_string()) {
-                Err(LineParseError::DetailBsbClient(
-                    *line_count,
-                    bsb.to_string(),
-                ))
+            if !i
+                .parse::<Bsb>()
+                .is_ok_and(|bsb| directory.contains(bsb.as_str()))
+            {
+                Err(LineParseError::DetailBsbClient(*line_count, i))
             } else {
                 Ok(())
             }
@@ -311,18 +324,104 @@ pub fn validate_lodge_ref(
     }
 }
 
+/// Text fields must stay within the BECS character set
+pub fn validate_becs_charset(
+    i: String,
+    fill_type: ValidationType,
+    line_count: &u32,
+) -> Result<(), LineParseError> {
+    let chars = non_becs_chars(&i);
+    if chars.is_empty() {
+        return Ok(());
+    }
+
+    match fill_type {
+        ValidationType::DescriptiveSelfName => {
+            Err(LineParseError::DescriptiveSelfNameCharset(chars))
+        }
+        ValidationType::DescriptiveEntry => Err(LineParseError::DescriptiveFileEntryCharset(chars)),
+        ValidationType::DetailClientName => {
+            Err(LineParseError::DetailClientNameCharset(*line_count, chars))
+        }
+        ValidationType::DetailLodgeRef => {
+            Err(LineParseError::DetailLodgeRefCharset(*line_count, chars))
+        }
+        ValidationType::DetailRemitter => {
+            Err(LineParseError::DetailRemitterCharset(*line_count, chars))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The settlement date must be a business day that has not passed, malformed dates pass
+/// as they are reported by `validate_date`
+pub fn validate_processing_day(
+    i: String,
+    calendar: &HolidayCalendar,
+    today: Date,
+    window: &CenturyWindow,
+) -> Result<(), LineParseError> {
+    let Some(date) = window.date(&i) else {
+        return Ok(());
+    };
+    let ddmmyy = |date: Date| SettleDate(date).to_string();
+
+    if date.lt(&today) {
+        return Err(LineParseError::DescriptiveSettleDatePast(
+            i,
+            ddmmyy(calendar.next_business_day(today)),
+        ));
+    }
+    match calendar.closed_reason(date) {
+        Some(reason) => Err(LineParseError::DescriptiveSettleDateClosed(
+            i,
+            reason,
+            ddmmyy(calendar.next_business_day(date)),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The bank named in the descriptive record must own the trace BSB, unknown institutions pass.
+/// An institution only guessed from the BSB prefix gives its own error for callers to warn
+pub fn validate_bank_trace_bsb(
+    bank: String,
+    bsb: String,
+    line_count: &u32,
+    directory: &BsbDirectory,
+) -> Result<(), LineParseError> {
+    match directory.institution(&bsb) {
+        Some(institution) if !institution.mnemonic().eq(bank.trim()) => match institution {
+            Institution::Listed(mnemonic) => Err(LineParseError::DescriptiveBankTraceBsb(
+                bank,
+                bsb,
+                *line_count,
+                mnemonic,
+            )),
+            Institution::Guessed(mnemonic) => Err(LineParseError::DescriptiveBankTraceBsbGuessed(
+                bank,
+                bsb,
+                *line_count,
+                mnemonic.to_owned(),
+            )),
+        },
+        _ => Ok(()),
+    }
+}
+
 pub fn validate_bsb_trace(
     i: String,
     fill_type: BsbType,
     line_count: &u32,
+    directory: &BsbDirectory,
 ) -> Result<(), LineParseError> {
-    let (_, bsb) = Bsb::deserialise(&i).unwrap();
-    let file = include_str!("data/bsb");
-
     match fill_type {
         BsbType::DetailTraceBsb => {
-            if !file.contains(&bsb.to_string()) {
-                Err(LineParseError::DetailBsbTrace(*line_count, bsb.to_string()))
+            if !i
+                .parse::<Bsb>()
+                .is_ok_and(|bsb| directory.contains(bsb.as_str()))
+            {
+                Err(LineParseError::DetailBsbTrace(*line_count, i))
             } else {
                 Ok(())
             }
@@ -420,40 +519,90 @@ pub fn validate_total_field(i: String, amount_type: TotalAmountType) -> Result<(
     }
 }
 
+/// Cross-checks the Total-Credit-Debit triplet against the sums of the detail amounts
+pub fn validate_detail_sums(
+    i: String,
+    credit: Cents,
+    debit: Cents,
+    amount_type: TotalAmountType,
+) -> Result<(), LineParseError> {
+    let (_, total_field) = TotalField::deserialise(&i).map_err(|_| LineParseError::TotalField)?;
+
+    let (claimed, actual) = match amount_type {
+        TotalAmountType::Total => (&total_field.total, credit.abs_diff(debit)),
+        TotalAmountType::Credit => (&total_field.credit, credit),
+        TotalAmountType::Debit => (&total_field.debit, debit),
+    };
+
+    // Non-numeric fields are reported by `validate_total_field` already
+    let claimed = match Cents::from_field(claimed, 10usize) {
+        Some(cents) if validate_number(claimed) => cents,
+        _ => return Ok(()),
+    };
+
+    if claimed.eq(&actual) {
+        return Ok(());
+    }
+
+    let (claimed, actual) = (claimed.to_string(), actual.to_string());
+    match amount_type {
+        TotalAmountType::Total => Err(LineParseError::TotalNetMismatch(claimed, actual)),
+        TotalAmountType::Credit => Err(LineParseError::TotalCreditMismatch(claimed, actual)),
+        TotalAmountType::Debit => Err(LineParseError::TotalDebitMismatch(claimed, actual)),
+    }
+}
+
+pub fn validate_balanced(i: String) -> Result<(), LineParseError> {
+    let (_, total_field) = TotalField::deserialise(&i).map_err(|_| LineParseError::TotalField)?;
+
+    if !validate_number(&total_field.total) {
+        Err(LineParseError::TotalField)
+    } else if !Cents::from_field(&total_field.total, 10usize).is_some_and(|t| t.eq(&Cents(0u64))) {
+        Err(LineParseError::TotalNotBalanced)
+    } else {
+        Ok(())
+    }
+}
+
 pub fn validate_record_type_count(i: String, line_count: &u32) -> Result<(), LineParseError> {
-    match validate_number(&i) {
-        true => {
-            let record_count = i.trim_start_matches('0').parse::<u32>().unwrap();
+    match validate_nonzero_str(&i).filter(|_| validate_number(&i)) {
+        Some(record_count) => {
             if !record_count.eq(line_count) {
                 Err(LineParseError::TotalCount(record_count, *line_count))
             } else {
                 Ok(())
             }
         }
-        false => Err(LineParseError::TotalNonNumeric),
+        None => Err(LineParseError::TotalNonNumeric),
     }
 }
 
 pub fn validate_trim_then_compute(credit: &str, debit: &str, total: &str) -> bool {
-    let credit = validate_nonzero_str(credit);
-    let debit = validate_nonzero_str(debit);
-    let total = validate_nonzero_str(total);
-
-    credit.sub(debit).eq(&total)
+    match (
+        Cents::from_field(credit, 10usize),
+        Cents::from_field(debit, 10usize),
+        Cents::from_field(total, 10usize),
+    ) {
+        (Some(credit), Some(debit), Some(total)) => credit.abs_diff(debit).eq(&total),
+        _ => false,
+    }
 }
 
-pub fn validate_nonzero_str(i: &str) -> u32 {
-    match i.trim_start_matches('0').eq("") {
-        true => 0u32,
-        false => return i.trim_start_matches('0').parse::<u32>().unwrap(),
+/// Parses zero-padded digits, None when they are not a number that fits in u32
+pub fn validate_nonzero_str(i: &str) -> Option<u32> {
+    match i.trim_start_matches('0').is_empty() {
+        true => Some(0u32),
+        false => i.trim_start_matches('0').parse::<u32>().ok(),
     }
 }
 
 pub fn validate_date_format(i: &str) -> bool {
-    let (_, date) = TwoDigitYears::deserialise(i).unwrap();
-    let format = Fd::parse("[day][month][year]").unwrap();
+    validate_date_format_in(i, &CenturyWindow::default())
+}
 
-    Td::parse(&date, &format).is_ok()
+/// Same as `validate_date_format` for the years of the given window
+pub fn validate_date_format_in(i: &str, window: &CenturyWindow) -> bool {
+    i.len().eq(&6usize) && window.date(i).is_some()
 }
 
 pub fn validate_number(i: &str) -> bool {
@@ -469,31 +618,177 @@ pub fn validate_blank(i: &str) -> bool {
     RE_BLANK.is_match(i)
 }
 
+/// Uppercase letters, numerics, blanks and these symbols form the BECS character set
+pub fn is_becs_char(c: char) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || c.eq(&' ') || BECS_SYMBOLS.contains(c)
+}
+
+/// Characters outside the BECS character set, each quoted once in order of appearance
+pub fn non_becs_chars(i: &str) -> String {
+    quote_chars(i, is_becs_char)
+}
+
+/// Characters outside ASCII, each quoted once in order of appearance
+pub fn non_ascii_chars(i: &str) -> String {
+    quote_chars(i, |c| c.is_ascii())
+}
+
+fn quote_chars(i: &str, allowed: impl Fn(char) -> bool) -> String {
+    let mut chars: Vec<char> = Vec::new();
+    for c in i.chars().filter(|c| !allowed(*c)) {
+        if !chars.contains(&c) {
+            chars.push(c);
+        }
+    }
+
+    chars
+        .iter()
+        .map(|c| format!("'{}'", c))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 #[test]
 fn test_validate_trim_then_compute() {
-    assert_eq!(validate_trim_then_compute("00032", "00000", "00031"), false)
+    assert!(!validate_trim_then_compute("00032", "00000", "00031"));
+    assert!(validate_trim_then_compute(
+        "0000000010",
+        "0000000032",
+        "0000000022"
+    ));
+    assert!(!validate_trim_then_compute(
+        "      0010",
+        "0000000032",
+        "0000000022"
+    ));
+    assert!(validate_trim_then_compute(
+        "9999999999",
+        "0000000000",
+        "9999999999"
+    ))
+}
+
+#[test]
+fn test_validate_becs_charset() {
+    assert!(is_becs_char('&') && is_becs_char('\\') && is_becs_char('A'));
+    assert!(!is_becs_char('`') && !is_becs_char('~') && !is_becs_char('é') && !is_becs_char('a'));
+    assert!(validate_becs_charset(
+        "O'BRIEN & SONS".to_owned(),
+        ValidationType::DetailClientName,
+        &2
+    )
+    .is_ok());
+    let e = validate_becs_charset("JOSé ✓ é".to_owned(), ValidationType::DetailClientName, &2)
+        .unwrap_err();
+    assert_eq!(e.rule(), "ABA213");
+    assert!(e
+        .to_string()
+        .contains("it must only contain BECS characters, not 'é', '✓'"));
+    let e = validate_becs_charset("O'Brien".to_owned(), ValidationType::DetailClientName, &2)
+        .unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("it must only contain BECS characters, not 'r', 'i', 'e', 'n'"));
+}
+
+#[test]
+fn test_validate_processing_day() {
+    let calendar = HolidayCalendar::new(Some(AuState::Nsw));
+    let today = Date::from_calendar_date(2024i32, time::Month::October, 1u8).unwrap();
+    let window = CenturyWindow::default();
+    assert!(validate_processing_day("081024".to_owned(), &calendar, today, &window).is_ok());
+    assert!(validate_processing_day("xx1024".to_owned(), &calendar, today, &window).is_ok());
+    let e = validate_processing_day("051024".to_owned(), &calendar, today, &window).unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("falls on a Saturday, which is not a processing day, use 081024 instead"));
+    let e = validate_processing_day("300924".to_owned(), &calendar, today, &window).unwrap_err();
+    assert_eq!(e.rule(), "ABA113");
+    assert!(e.to_string().contains("use 011024 instead"));
+
+    let window = CenturyWindow::new(1900i32).unwrap();
+    let e = validate_processing_day("081024".to_owned(), &calendar, today, &window).unwrap_err();
+    assert_eq!(e.rule(), "ABA113");
+}
+
+#[test]
+fn test_validate_bank_trace_bsb() {
+    let directory = BsbDirectory::embedded();
+    assert!(
+        validate_bank_trace_bsb("CBA".to_owned(), "062-000".to_owned(), &2, &directory).is_ok()
+    );
+    assert!(
+        validate_bank_trace_bsb("XYZ".to_owned(), "999-999".to_owned(), &2, &directory).is_ok()
+    );
+    let e = validate_bank_trace_bsb("NAB".to_owned(), "062-000".to_owned(), &2, &directory)
+        .unwrap_err();
+    assert!(matches!(
+        e,
+        LineParseError::DescriptiveBankTraceBsbGuessed(..)
+    ));
+
+    let directory: BsbDirectory = "062-000,CBA,Sydney,,,NSW,2000,PEH\n".parse().unwrap();
+    let e = validate_bank_trace_bsb("NAB".to_owned(), "062-000".to_owned(), &2, &directory)
+        .unwrap_err();
+    assert!(e
+        .to_string()
+        .contains("which belongs to CBA, use CBA instead"));
+}
+
+#[test]
+fn test_validate_detail_sums() {
+    let i = "000100000000010000000000000000".to_owned();
+    assert!(
+        validate_detail_sums(i.clone(), Cents(900000), Cents(0), TotalAmountType::Credit).is_err()
+    );
+    assert!(
+        validate_detail_sums(i.clone(), Cents(1000000), Cents(0), TotalAmountType::Credit).is_ok()
+    );
+    assert!(validate_detail_sums(i, Cents(900000), Cents(0), TotalAmountType::Debit).is_ok());
+
+    let malformed_total = "00010000  00010000000000000000".to_owned();
+    assert!(matches!(
+        validate_detail_sums(
+            malformed_total,
+            Cents(900000),
+            Cents(0),
+            TotalAmountType::Credit
+        ),
+        Err(LineParseError::TotalCreditMismatch(..))
+    ));
+}
+
+#[test]
+fn test_validate_balanced() {
+    assert!(validate_balanced("000000000000003129240000312924".to_owned()).is_ok());
+    assert!(validate_balanced("000000000100003129240000312923".to_owned()).is_err());
 }
 
 #[test]
 fn test_validate_non_zero_str() {
     let i: &str = "00000";
-    assert_eq!(validate_nonzero_str(i), 0u32)
+    assert_eq!(validate_nonzero_str(i), Some(0u32));
+    assert_eq!(validate_nonzero_str("99999999999"), None);
+    assert!(matches!(
+        validate_record_type_count("+00004".to_owned(), &4u32),
+        Err(LineParseError::TotalNonNumeric)
+    ))
 }
 
 #[test]
 fn test_validate_date_format() {
     let ddmmyy: &str = "300220";
-    assert_eq!(validate_date_format(ddmmyy), false)
+    assert!(!validate_date_format(ddmmyy))
 }
 
 #[test]
 fn test_validate_number() {
     let i: &str = "abcde";
-    assert_eq!(validate_number(i), false)
+    assert!(!validate_number(i))
 }
 
 #[test]
 fn test_validate_blank() {
     let i: &str = "oolala";
-    assert_eq!(validate_blank(i), false)
+    assert!(!validate_blank(i))
 }
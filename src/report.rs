@@ -0,0 +1,392 @@
+//! Structured validation diagnostics collected from .aba file validation
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use crate::errors::*;
+
+/// Severity of a diagnostic
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// The .aba block a diagnostic was raised in
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockKind {
+    Descriptive,
+    Detail,
+    Total,
+}
+
+/// Output formats a validation report can be rendered into
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default, ValueEnum)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Json,
+    Junit,
+    Sarif,
+}
+
+/// A single validation finding with its exact location in the file
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+pub struct Diagnostic {
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based inclusive column where the field starts
+    pub start_column: u32,
+    /// 1-based inclusive column where the field ends
+    pub end_column: u32,
+    pub block: BlockKind,
+    pub field: &'static str,
+    /// The offending field value as found in the file
+    pub value: String,
+    /// Stable rule code, see `LineParseError::rule`
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(e: &LineParseError, line: u32, value: &str) -> Self {
+        let (start_column, end_column) = e.columns();
+
+        Self {
+            line,
+            start_column,
+            end_column,
+            block: e.block(),
+            field: e.field(),
+            value: value.to_owned(),
+            rule: e.rule(),
+            severity: Severity::Error,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Same as `new` for findings that do not invalidate the file
+    pub fn warning(e: &LineParseError, line: u32, value: &str) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::new(e, line, value)
+        }
+    }
+
+    /// The message without the list marker and trailing line break used by the text report
+    pub fn summary(&self) -> &str {
+        self.message.trim_start_matches("- ").trim_end()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// All diagnostics collected from validating a .aba file
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Serialize)]
+pub struct ValidationReport {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    /// Renders the report in `format` and writes it to `path`, `source` names the validated file
+    pub async fn write(
+        &self,
+        path: impl AsRef<Path>,
+        format: ReportFormat,
+        source: &str,
+    ) -> Result<(), CemtexerError> {
+        tokio::fs::write(path, self.render(format, source)).await?;
+
+        Ok(())
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn filter<'a>(
+        &'a self,
+        pred: impl Fn(&Diagnostic) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a Diagnostic> + 'a {
+        self.diagnostics.iter().filter(move |d| pred(d))
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn count_severity(&self, severity: Severity) -> usize {
+        self.filter(move |d| d.severity.eq(&severity)).count()
+    }
+
+    pub fn count_block(&self, block: BlockKind) -> usize {
+        self.filter(move |d| d.block.eq(&block)).count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count_severity(Severity::Error).gt(&0usize)
+    }
+
+    /// Process exit status for the command line, 1 when any error was found and 0 otherwise,
+    /// warnings alone do not fail the check
+    pub fn exit_code(&self) -> i32 {
+        match self.has_errors() {
+            true => 1,
+            false => 0,
+        }
+    }
+}
+
+impl ValidationReport {
+    /// Error and warning counts broken down per block
+    pub fn summary(&self) -> String {
+        let mut res = format!(
+            "Summary: {} error(s), {} warning(s)\n",
+            self.count_severity(Severity::Error),
+            self.count_severity(Severity::Warning)
+        );
+        for (name, block) in [
+            ("descriptive", BlockKind::Descriptive),
+            ("detail", BlockKind::Detail),
+            ("total", BlockKind::Total),
+        ] {
+            res.push_str(&format!(
+                "  {}: {} error(s)\n",
+                name,
+                self.filter(move |d| d.block.eq(&block) && d.severity.eq(&Severity::Error))
+                    .count()
+            ));
+        }
+        res
+    }
+
+    /// Renders the report in the requested format, `source` names the validated file
+    pub fn render(&self, format: ReportFormat, source: &str) -> String {
+        match format {
+            ReportFormat::Text => match self.is_empty() {
+                true => "No errors detected".to_owned(),
+                false => self.to_string(),
+            },
+            ReportFormat::Json => self.render_json(source),
+            ReportFormat::Junit => self.render_junit(source),
+            ReportFormat::Sarif => self.render_sarif(source),
+        }
+    }
+
+    fn render_json(&self, source: &str) -> String {
+        let doc = json!({
+            "source": source,
+            "valid": !self.has_errors(),
+            "errors": self.count_severity(Severity::Error),
+            "warnings": self.count_severity(Severity::Warning),
+            "diagnostics": self.diagnostics.iter().map(|d| json!({
+                "line": d.line,
+                "start_column": d.start_column,
+                "end_column": d.end_column,
+                "block": d.block,
+                "field": d.field,
+                "value": d.value,
+                "rule": d.rule,
+                "severity": d.severity,
+                "message": d.summary(),
+            })).collect::<Vec<Value>>(),
+        });
+
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+
+    fn render_junit(&self, source: &str) -> String {
+        let mut cases = String::new();
+        let failures = self.count_severity(Severity::Error);
+
+        for d in self.diagnostics.iter() {
+            let kind = match d.severity {
+                Severity::Error => "failure",
+                Severity::Warning => "system-out",
+            };
+            cases.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{} {} at line {}, columns {}-{}\">\n",
+                xml_escape(&format!("{:?}", d.block).to_lowercase()),
+                d.rule,
+                d.field,
+                d.line,
+                d.start_column,
+                d.end_column
+            ));
+            match d.severity {
+                Severity::Error => cases.push_str(&format!(
+                    "      <{0} type=\"{1}\" message=\"{2}\">value: {3}</{0}>\n",
+                    kind,
+                    d.rule,
+                    xml_escape(d.summary()),
+                    xml_escape(&d.value)
+                )),
+                Severity::Warning => cases.push_str(&format!(
+                    "      <{0}>{1}</{0}>\n",
+                    kind,
+                    xml_escape(d.summary())
+                )),
+            }
+            cases.push_str("    </testcase>\n");
+        }
+
+        if self.is_empty() {
+            cases.push_str("    <testcase classname=\"abacheck\" name=\"content validation\"/>\n");
+        }
+
+        let tests = self.len().max(1usize);
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"abacheck\" tests=\"{0}\" failures=\"{1}\">\n  <testsuite name=\"{2}\" tests=\"{0}\" failures=\"{1}\">\n{3}  </testsuite>\n</testsuites>\n",
+            tests,
+            failures,
+            xml_escape(source),
+            cases
+        )
+    }
+
+    fn render_sarif(&self, source: &str) -> String {
+        let mut rules: BTreeMap<&str, &Diagnostic> = BTreeMap::new();
+        for d in self.diagnostics.iter() {
+            rules.entry(d.rule).or_insert(d);
+        }
+
+        let doc = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                        "rules": rules.values().map(|d| json!({
+                            "id": d.rule,
+                            "name": d.field,
+                            "shortDescription": { "text": format!("{:?} block field {}", d.block, d.field) },
+                        })).collect::<Vec<Value>>(),
+                    }
+                },
+                "results": self.diagnostics.iter().map(|d| json!({
+                    "ruleId": d.rule,
+                    "level": match d.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    },
+                    "message": { "text": d.summary() },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": source },
+                            "region": {
+                                "startLine": d.line,
+                                "startColumn": d.start_column,
+                                "endColumn": d.end_column + 1u32,
+                            }
+                        }
+                    }],
+                })).collect::<Vec<Value>>(),
+            }],
+        });
+
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+}
+
+fn xml_escape(i: &str) -> String {
+    i.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl IntoIterator for ValidationReport {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in self.diagnostics.iter() {
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_validation_report() {
+    let mut report = ValidationReport::new();
+    report.push(Diagnostic::new(
+        &LineParseError::DetailAmount(2),
+        2,
+        "12345     ",
+    ));
+    report.push(Diagnostic::new(&LineParseError::TotalBlankOne, 4, "x"));
+
+    assert_eq!(report.len(), 2);
+    assert!(report.has_errors());
+    assert_eq!(report.count_block(BlockKind::Detail), 1);
+
+    let amount = report.filter(|d| d.rule.eq("ABA206")).next().unwrap();
+    assert_eq!((amount.start_column, amount.end_column), (21, 30));
+    assert_eq!(amount.field, "amount");
+    assert!(report.to_string().starts_with("- At line 2"));
+    assert!(report.summary().contains("  detail: 1 error(s)"));
+}
+
+#[test]
+fn test_render_report() {
+    let mut report = ValidationReport::new();
+    report.push(Diagnostic::new(&LineParseError::DetailIndicator(3), 3, "<"));
+
+    let json: Value = serde_json::from_str(&report.render(ReportFormat::Json, "a.aba")).unwrap();
+    assert_eq!(json["diagnostics"][0]["rule"], "ABA204");
+    assert_eq!(json["valid"], false);
+
+    let sarif: Value = serde_json::from_str(&report.render(ReportFormat::Sarif, "a.aba")).unwrap();
+    let region = &sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"];
+    assert_eq!(region["startColumn"], 18);
+    assert_eq!(region["endColumn"], 19);
+
+    let junit = report.render(ReportFormat::Junit, "a.aba");
+    assert!(junit.contains("failures=\"1\""));
+    assert!(junit.contains("value: &lt;"));
+
+    assert_eq!(
+        ValidationReport::new().render(ReportFormat::Text, "a.aba"),
+        "No errors detected"
+    );
+}
@@ -1,4 +1,8 @@
 //! Various dispatch helpers for data validations that share common functions
+use clap::ValueEnum;
+use std::str::FromStr;
+
+use crate::errors::*;
 
 /// Dispatch types for .aba file validation that have one-to-one mapping
 #[non_exhaustive]
@@ -55,3 +59,190 @@ pub enum TotalAmountType {
     Credit,
     Debit,
 }
+
+/// Dispatch types for reading .aba content, lenient mode pads or truncates mis-sized lines
+/// and reports them as diagnostics instead of rejecting the whole file
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ParseMode {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+/// Dispatch types for csv text fields, normalise transliterates and uppercases them into the
+/// BECS character set instead of leaving invalid characters to validation
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TextMode {
+    #[default]
+    Verbatim,
+    Normalise,
+}
+
+/// What to do with csv text fields longer than their .aba field
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Copy, Default, ValueEnum)]
+pub enum OverflowPolicy {
+    /// Fail validation, the batch is not generated
+    #[default]
+    Reject,
+    /// Cut the field to size and warn
+    Truncate,
+    /// Shorten common words such as PTY LIMITED to PL first, then truncate if still too long
+    Abbreviate,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "" | "REJECT" => Ok(OverflowPolicy::Reject),
+            "TRUNCATE" => Ok(OverflowPolicy::Truncate),
+            "ABBREVIATE" => Ok(OverflowPolicy::Abbreviate),
+            _ => Err(CemtexerError::Template(format!(
+                "`{}` is not a valid overflow policy, it must be one of REJECT, TRUNCATE or ABBREVIATE",
+                s
+            ))),
+        }
+    }
+}
+
+/// Dispatch types for importing APCA BSB extracts
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BsbImportKind {
+    /// Replaces the whole directory
+    Full,
+    /// Monthly changes applied on top of the current directory
+    Delta,
+}
+
+/// Line termination written between records of a generated .aba file
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Copy, Default, ValueEnum)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    /// Joins the records, the last one is terminated as well unless `final_newline` is false
+    pub fn join(&self, lines: &[String], final_newline: bool) -> String {
+        let mut res = lines.join(self.as_str());
+        if final_newline {
+            res.push_str(self.as_str());
+        }
+        res
+    }
+}
+
+impl FromStr for LineEnding {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "" | "LF" => Ok(LineEnding::Lf),
+            "CRLF" => Ok(LineEnding::Crlf),
+            _ => Err(CemtexerError::Template(format!(
+                "`{}` is not a valid line ending, it must be either LF or CRLF",
+                s
+            ))),
+        }
+    }
+}
+
+/// States and territories with their own public holidays
+#[non_exhaustive]
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Copy, ValueEnum)]
+pub enum AuState {
+    Act,
+    Nsw,
+    Nt,
+    Qld,
+    Sa,
+    Tas,
+    Vic,
+    Wa,
+}
+
+impl AuState {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuState::Act => "ACT",
+            AuState::Nsw => "NSW",
+            AuState::Nt => "NT",
+            AuState::Qld => "QLD",
+            AuState::Sa => "SA",
+            AuState::Tas => "TAS",
+            AuState::Vic => "VIC",
+            AuState::Wa => "WA",
+        }
+    }
+}
+
+impl FromStr for AuState {
+    type Err = CemtexerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "ACT" => Ok(AuState::Act),
+            "NSW" => Ok(AuState::Nsw),
+            "NT" => Ok(AuState::Nt),
+            "QLD" => Ok(AuState::Qld),
+            "SA" => Ok(AuState::Sa),
+            "TAS" => Ok(AuState::Tas),
+            "VIC" => Ok(AuState::Vic),
+            "WA" => Ok(AuState::Wa),
+            _ => Err(CemtexerError::Template(format!(
+                "`{}` is not a valid state, it must be one of ACT, NSW, NT, QLD, SA, TAS, VIC or WA",
+                s
+            ))),
+        }
+    }
+}
+
+#[test]
+fn test_line_ending() {
+    let lines = vec!["0".to_owned(), "7".to_owned()];
+    assert_eq!(LineEnding::Crlf.join(&lines, true), "0\r\n7\r\n");
+    assert_eq!(LineEnding::Lf.join(&lines, false), "0\n7");
+    assert_eq!("crlf".parse::<LineEnding>().unwrap(), LineEnding::Crlf);
+    assert!("CR".parse::<LineEnding>().is_err());
+}
+
+#[test]
+fn test_overflow_policy() {
+    assert_eq!(
+        "".parse::<OverflowPolicy>().unwrap(),
+        OverflowPolicy::Reject
+    );
+    assert_eq!(
+        "abbreviate".parse::<OverflowPolicy>().unwrap(),
+        OverflowPolicy::Abbreviate
+    );
+    assert!("cut".parse::<OverflowPolicy>().is_err());
+}
+
+#[test]
+fn test_au_state() {
+    assert_eq!(" vic".parse::<AuState>().unwrap(), AuState::Vic);
+    assert_eq!(AuState::Nsw.code(), "NSW");
+    assert!("NZ".parse::<AuState>().is_err());
+}